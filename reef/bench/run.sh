@@ -0,0 +1,12 @@
+#!/bin/bash
+# times every benchmark on both backends: reef/bench/run.sh [reef binary]
+set -e
+cd "$(dirname "$0")"
+reef=${1:-../../target/release/reef}
+TIMEFORMAT=%R
+for script in *.reef; do
+    for backend in tree vm; do
+        printf '%-12s %-5s ' "$script" "$backend"
+        { time "$reef" run "$script" --backend "$backend" >/dev/null; } 2>&1
+    done
+done
@@ -1,4 +1,4 @@
-use crate::{Literal, expr::ExprKind};
+use crate::{Literal, Token, expr::ExprKind, stmt::StmtKind};
 
 pub struct AstPrinter;
 
@@ -9,6 +9,11 @@ impl AstPrinter {
                 left,
                 operator,
                 right,
+            }
+            | ExprKind::Logical {
+                left,
+                operator,
+                right,
             } => {
                 format!(
                     "({} {} {})",
@@ -23,10 +28,154 @@ impl AstPrinter {
             ExprKind::Unary { operator, right } => {
                 format!("({} {})", operator.lexeme, Self::print(right))
             }
-            ExprKind::Literal { value } => Self::print_literal(value),
+            ExprKind::Literal { value, .. } => Self::print_literal(value),
+            ExprKind::Variable { name, .. } => name.lexeme.to_string(),
+            ExprKind::Assign { name, value, .. } => {
+                format!("(= {} {})", name.lexeme, Self::print(value))
+            }
+            ExprKind::Call {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                let mut parts = vec![Self::print(callee)];
+                parts.extend(arguments.iter().map(Self::print));
+                parts.extend(
+                    named
+                        .iter()
+                        .map(|(name, arg)| format!("{}: {}", name.lexeme, Self::print(arg))),
+                );
+                format!("(call {})", parts.join(" "))
+            }
+            ExprKind::Function {
+                parameters,
+                defaults,
+                rest,
+                body,
+                ..
+            } => {
+                let parameters = Self::print_parameters(parameters, defaults, rest.as_ref());
+                Self::print_block(&format!("fun ({})", parameters), body)
+            }
+            ExprKind::Get { object, name } => {
+                format!("(get {} {})", Self::print(object), name.lexeme)
+            }
+            ExprKind::Set {
+                object,
+                name,
+                value,
+            } => format!(
+                "(set {} {} {})",
+                Self::print(object),
+                name.lexeme,
+                Self::print(value)
+            ),
+            // a missing initializer or return value
+            ExprKind::None => String::from("nil"),
             _ => String::from("expression not implemented yet"),
         }
     }
+    /// statements as s-expressions, used by `--dump-ast`
+    pub fn print_stmt(stmt: &StmtKind) -> String {
+        match stmt {
+            StmtKind::Print { expr } => format!("(print {})", Self::print(expr)),
+            StmtKind::Expression { expr } => format!("(expr {})", Self::print(expr)),
+            StmtKind::Var { name, initializer } => {
+                format!("(var {} {})", name.lexeme, Self::print(initializer))
+            }
+            StmtKind::Block { statements } => Self::print_block("block", statements),
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match else_branch {
+                Some(else_branch) => format!(
+                    "(if {} {} {})",
+                    Self::print(condition),
+                    Self::print_stmt(then_branch),
+                    Self::print_stmt(else_branch)
+                ),
+                None => format!(
+                    "(if {} {})",
+                    Self::print(condition),
+                    Self::print_stmt(then_branch)
+                ),
+            },
+            StmtKind::While { condition, body } => {
+                format!(
+                    "(while {} {})",
+                    Self::print(condition),
+                    Self::print_stmt(body)
+                )
+            }
+            StmtKind::Function {
+                name,
+                parameters,
+                defaults,
+                rest,
+                body,
+            } => {
+                let parameters = Self::print_parameters(parameters, defaults, rest.as_ref());
+                let head = format!("fun {} ({})", name.lexeme, parameters);
+                Self::print_block(&head, body)
+            }
+            StmtKind::Return { value, .. } => format!("(return {})", Self::print(value)),
+            StmtKind::Import {
+                path, alias, names, ..
+            } => {
+                let mut parts = vec![format!("{:?}", path)];
+                match alias {
+                    Some(alias) => parts.push(format!("as {}", alias.lexeme)),
+                    None => parts.extend(names.iter().map(|name| name.lexeme.to_string())),
+                }
+                format!("(import {})", parts.join(" "))
+            }
+            StmtKind::Throw { value, .. } => format!("(throw {})", Self::print(value)),
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                let mut parts = vec![String::from("(try"), Self::print_block("block", body)];
+                if let Some((name, handler)) = catch {
+                    parts.push(Self::print_block(
+                        &format!("catch {}", name.lexeme),
+                        handler,
+                    ));
+                }
+                if let Some(finally) = finally {
+                    parts.push(Self::print_block("finally", finally));
+                }
+                format!("{})", parts.join(" "))
+            }
+            StmtKind::Error { .. } => String::from("(error)"),
+        }
+    }
+    // `a (= b 2) ...rest`
+    fn print_parameters(
+        parameters: &[Token],
+        defaults: &[ExprKind],
+        rest: Option<&Token>,
+    ) -> String {
+        let required = parameters.len() - defaults.len();
+        let mut parts: Vec<String> = parameters
+            .iter()
+            .enumerate()
+            .map(|(index, param)| match index.checked_sub(required) {
+                Some(index) => format!("(= {} {})", param.lexeme, Self::print(&defaults[index])),
+                None => param.lexeme.to_string(),
+            })
+            .collect();
+        parts.extend(rest.map(|rest| format!("...{}", rest.lexeme)));
+        parts.join(" ")
+    }
+    fn print_block(head: &str, statements: &[StmtKind]) -> String {
+        let mut parts = vec![head.to_string()];
+        parts.extend(statements.iter().map(Self::print_stmt));
+        format!("({})", parts.join(" "))
+    }
     pub fn print_literal(literal: &Literal) -> String {
         match literal {
             Literal::Number(n) => n.to_string(),
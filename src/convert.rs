@@ -0,0 +1,335 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    error::ReefError,
+    expr::Value,
+    list::{List, list_type},
+    map::{Map, map_type},
+    object::HostObject,
+};
+
+/// rust types a reef value can be read as. the error says what was
+/// expected, the caller adds where
+pub trait FromReef: Sized {
+    fn from_reef(value: Value) -> Result<Self, String>;
+}
+
+/// rust types that turn into a reef value
+pub trait IntoReef {
+    fn into_reef(self) -> Value;
+}
+
+fn mismatch(expected: &str, value: &Value) -> String {
+    format!("expected {}, got {}", expected, value.type_name())
+}
+
+impl FromReef for Value {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+impl IntoReef for Value {
+    fn into_reef(self) -> Value {
+        self
+    }
+}
+
+impl FromReef for f64 {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Number(n) => Ok(n),
+            _ => Err(mismatch("number", &value)),
+        }
+    }
+}
+
+impl IntoReef for f64 {
+    fn into_reef(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl FromReef for f32 {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        f64::from_reef(value).map(|n| n as f32)
+    }
+}
+
+impl IntoReef for f32 {
+    fn into_reef(self) -> Value {
+        Value::Number(self as f64)
+    }
+}
+
+// reef only has doubles, so integers must be whole and in range
+macro_rules! integer_conversions {
+    ($($int:ty),*) => {$(
+        impl FromReef for $int {
+            fn from_reef(value: Value) -> Result<Self, String> {
+                let n = f64::from_reef(value)?;
+                if n.fract() != 0.0 || n < <$int>::MIN as f64 || n > <$int>::MAX as f64 {
+                    return Err(format!("expected {}, got {}", stringify!($int), n));
+                }
+                Ok(n as $int)
+            }
+        }
+
+        impl IntoReef for $int {
+            fn into_reef(self) -> Value {
+                Value::Number(self as f64)
+            }
+        }
+    )*};
+}
+
+integer_conversions!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl FromReef for bool {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(mismatch("boolean", &value)),
+        }
+    }
+}
+
+impl IntoReef for bool {
+    fn into_reef(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl FromReef for Rc<str> {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        match value {
+            Value::String(s) => Ok(s),
+            _ => Err(mismatch("string", &value)),
+        }
+    }
+}
+
+impl IntoReef for Rc<str> {
+    fn into_reef(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl FromReef for String {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        Rc::<str>::from_reef(value).map(|s| s.to_string())
+    }
+}
+
+impl IntoReef for String {
+    fn into_reef(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl IntoReef for &str {
+    fn into_reef(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl IntoReef for () {
+    fn into_reef(self) -> Value {
+        Value::Nil
+    }
+}
+
+impl FromReef for Rc<HostObject> {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Object(object) => Ok(object),
+            _ => Err(mismatch("object", &value)),
+        }
+    }
+}
+
+impl IntoReef for Rc<HostObject> {
+    fn into_reef(self) -> Value {
+        Value::Object(self)
+    }
+}
+
+/// nil reads as `None`
+impl<T: FromReef> FromReef for Option<T> {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::from_reef(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoReef> IntoReef for Option<T> {
+    fn into_reef(self) -> Value {
+        self.map_or(Value::Nil, IntoReef::into_reef)
+    }
+}
+
+/// a `List`, each item converted
+impl<T: FromReef> FromReef for Vec<T> {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        let list = List::of(&value).ok_or_else(|| mismatch("list", &value))?;
+        list.items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                T::from_reef(item.clone()).map_err(|e| format!("item {}: {}", index, e))
+            })
+            .collect()
+    }
+}
+
+impl<T: IntoReef> IntoReef for Vec<T> {
+    fn into_reef(self) -> Value {
+        let items = self.into_iter().map(IntoReef::into_reef).collect();
+        list_type().instance(List { items })
+    }
+}
+
+/// a `Map`, each value converted
+impl<T: FromReef> FromReef for HashMap<String, T> {
+    fn from_reef(value: Value) -> Result<Self, String> {
+        let map = Map::of(&value).ok_or_else(|| mismatch("map", &value))?;
+        map.entries
+            .iter()
+            .map(|(key, item)| {
+                T::from_reef(item.clone())
+                    .map(|item| (key.to_string(), item))
+                    .map_err(|e| format!("key '{}': {}", key, e))
+            })
+            .collect()
+    }
+}
+
+impl<T: IntoReef> IntoReef for HashMap<String, T> {
+    fn into_reef(self) -> Value {
+        let entries = self
+            .into_iter()
+            .map(|(key, item)| (key.into(), item.into_reef()))
+            .collect();
+        map_type().instance(Map { entries })
+    }
+}
+
+/// what a typed native can return: a value, or a result whose error
+/// becomes the script's runtime error
+pub trait IntoReefResult {
+    fn into_reef_result(self) -> Result<Value, ReefError>;
+}
+
+impl<T: IntoReef> IntoReefResult for T {
+    fn into_reef_result(self) -> Result<Value, ReefError> {
+        Ok(self.into_reef())
+    }
+}
+
+impl<T: IntoReef> IntoReefResult for Result<T, ReefError> {
+    fn into_reef_result(self) -> Result<Value, ReefError> {
+        self.map(IntoReef::into_reef)
+    }
+}
+
+/// ordinary rust functions usable as natives, with the arity taken from
+/// their parameter list. `Args` only tells the impls apart
+pub trait TypedFn<Args>: 'static {
+    const ARITY: usize;
+    fn call_typed(&self, arguments: Vec<Value>) -> Result<Result<Value, ReefError>, String>;
+}
+
+macro_rules! typed_fn {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> TypedFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoReefResult,
+            $($arg: FromReef),*
+        {
+            const ARITY: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call_typed(
+                &self,
+                arguments: Vec<Value>,
+            ) -> Result<Result<Value, ReefError>, String> {
+                if arguments.len() != Self::ARITY {
+                    return Err(format!(
+                        "expected {} arguments, got {}",
+                        Self::ARITY,
+                        arguments.len()
+                    ));
+                }
+                let mut arguments = arguments.into_iter().enumerate();
+                $(
+                    let (index, value) = arguments.next().expect("arity was checked");
+                    let $arg = $arg::from_reef(value)
+                        .map_err(|e| format!("argument {}: {}", index + 1, e))?;
+                )*
+                Ok(self($($arg),*).into_reef_result())
+            }
+        }
+    };
+}
+
+typed_fn!();
+typed_fn!(A);
+typed_fn!(A, B);
+typed_fn!(A, B, C);
+typed_fn!(A, B, C, D);
+typed_fn!(A, B, C, D, E);
+typed_fn!(A, B, C, D, E, G);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use super::{FromReef, IntoReef};
+    use crate::expr::Value;
+    use crate::interpreter::{is_equal, stringify};
+
+    #[test]
+    fn test_round_trips() {
+        assert_eq!(f64::from_reef(2.5.into_reef()), Ok(2.5));
+        assert_eq!(u8::from_reef(200u8.into_reef()), Ok(200));
+        assert!(u8::from_reef(Value::Number(256.0)).is_err());
+        assert!(i32::from_reef(Value::Number(1.5)).is_err());
+        assert_eq!(String::from_reef("hi".into_reef()), Ok("hi".to_string()));
+        assert_eq!(Option::<bool>::from_reef(Value::Nil), Ok(None));
+        assert_eq!(Option::<bool>::from_reef(true.into_reef()), Ok(Some(true)));
+        assert_eq!(
+            bool::from_reef(Value::String(Rc::from("x"))),
+            Err("expected boolean, got string".to_string())
+        );
+        assert!(matches!(None::<f64>.into_reef(), Value::Nil));
+    }
+
+    #[test]
+    fn test_collection_round_trips() {
+        let list = vec![1.0, 2.0, 3.0].into_reef();
+        assert_eq!(stringify(&list), "[1, 2, 3]");
+        assert_eq!(Vec::<f64>::from_reef(list.clone()), Ok(vec![1.0, 2.0, 3.0]));
+        assert!(is_equal(&list, &vec![1u8, 2, 3].into_reef()));
+        assert_eq!(
+            Vec::<bool>::from_reef(list),
+            Err("item 0: expected boolean, got number".to_string())
+        );
+        assert_eq!(
+            Vec::<String>::from_reef(Value::Nil),
+            Err("expected list, got nil".to_string())
+        );
+
+        let map = HashMap::from([("b".to_string(), vec![true]), ("a".to_string(), vec![])]);
+        let value = map.clone().into_reef();
+        assert_eq!(stringify(&value), "{a: [], b: [true]}");
+        assert_eq!(
+            HashMap::<String, Vec<bool>>::from_reef(value.clone()),
+            Ok(map)
+        );
+        assert!(HashMap::<String, f64>::from_reef(value).is_err());
+    }
+}
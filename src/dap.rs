@@ -0,0 +1,481 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use serde_json::{Value, json};
+
+use crate::{
+    Parser, Scanner,
+    environment::EnvRef,
+    error::ReefError,
+    interpreter::{Debugger, Interpreter},
+    lsp::transport::{read_message, write_message},
+    output::Capture,
+    stmt::StmtKind,
+};
+
+const THREAD_ID: u64 = 1;
+// variables references encode (frame, scope depth); globals get their own
+const GLOBALS_REFERENCE: usize = 1_000_000;
+const SCOPES_PER_FRAME: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StepMode {
+    Continue,
+    Entry,
+    StepIn,
+    StepOver(usize),
+    StepOut(usize),
+}
+
+struct Session<R, W> {
+    reader: R,
+    writer: W,
+    seq: u64,
+    path: String,
+    breakpoints: HashSet<usize>,
+    mode: StepMode,
+    // (line, depth) of the last statement run and of the last pause
+    last_position: Option<(usize, usize)>,
+    stopped_at: (usize, usize),
+    disconnected: bool,
+}
+
+/// debug adapter protocol server. runs the script on the current thread;
+/// while paused, the interpreter's debugger hook services requests itself
+pub struct DapServer<R, W> {
+    session: Rc<RefCell<Session<R, W>>>,
+    program: Vec<StmtKind>,
+}
+
+pub fn run_stdio() -> io::Result<()> {
+    DapServer::new(io::stdin().lock(), io::stdout().lock()).run()
+}
+
+impl<R: BufRead + 'static, W: Write + 'static> DapServer<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        DapServer {
+            session: Rc::new(RefCell::new(Session {
+                reader,
+                writer,
+                seq: 0,
+                path: String::new(),
+                breakpoints: HashSet::new(),
+                mode: StepMode::Continue,
+                last_position: None,
+                stopped_at: (0, 0),
+                disconnected: false,
+            })),
+            program: Vec::new(),
+        }
+    }
+
+    pub fn into_writer(self) -> W {
+        match Rc::try_unwrap(self.session) {
+            Ok(session) => session.into_inner().writer,
+            Err(_) => panic!("debug session still borrowed by the interpreter"),
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            let message = read_message(&mut self.session.borrow_mut().reader)?;
+            let Some(request) = message else {
+                return Ok(());
+            };
+            let command = request["command"].as_str().unwrap_or_default();
+            let arguments = &request["arguments"];
+            {
+                let mut session = self.session.borrow_mut();
+                match command {
+                    "initialize" => {
+                        session.respond(
+                            &request,
+                            json!({
+                                "supportsConfigurationDoneRequest": true,
+                                "supportsEvaluateForHovers": true,
+                            }),
+                        )?;
+                        session.event("initialized", json!({}))?;
+                    }
+                    "launch" => {
+                        let path = arguments["program"].as_str().unwrap_or_default();
+                        match load_program(path) {
+                            Ok(program) => {
+                                self.program = program;
+                                session.path = path.to_string();
+                                if arguments["stopOnEntry"].as_bool() == Some(true) {
+                                    session.mode = StepMode::Entry;
+                                }
+                                session.respond(&request, json!({}))?;
+                            }
+                            Err(message) => session.fail(&request, &message)?,
+                        }
+                    }
+                    "configurationDone" => {
+                        session.respond(&request, json!({}))?;
+                        drop(session);
+                        self.run_program()?;
+                    }
+                    "disconnect" | "terminate" => {
+                        session.respond(&request, json!({}))?;
+                        return Ok(());
+                    }
+                    _ => session.handle_common(&request, None)?,
+                }
+            }
+            if self.session.borrow().disconnected {
+                return Ok(());
+            }
+        }
+    }
+
+    fn run_program(&mut self) -> io::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_debugger(Box::new(DapHook {
+            session: Rc::clone(&self.session),
+        }));
+        interpreter.set_output(Box::new(OutputEvents {
+            session: Rc::clone(&self.session),
+        }));
+        let result = interpreter.interpret(std::mem::take(&mut self.program));
+        drop(interpreter);
+
+        let mut session = self.session.borrow_mut();
+        if session.disconnected {
+            return Ok(());
+        }
+        let exit_code = match result {
+            Ok(()) => 0,
+            Err(e) => {
+                let output = format!("{}\n", e.message());
+                session.event("output", json!({ "category": "stderr", "output": output }))?;
+                70
+            }
+        };
+        session.event("exited", json!({ "exitCode": exit_code }))?;
+        session.event("terminated", json!({}))
+    }
+}
+
+fn load_program(path: &str) -> Result<Vec<StmtKind>, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    let tokens = Scanner::new(source).scan_tokens();
+    Parser::new(tokens)
+        .parse()
+        .map_err(|e| e.message().to_string())
+}
+
+impl<R: BufRead, W: Write> Session<R, W> {
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.writer, &message)
+    }
+
+    fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Value, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": false,
+            "command": request["command"],
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    // requests that make sense whether or not the program is paused
+    fn handle_common(
+        &mut self,
+        request: &Value,
+        interpreter: Option<&mut Interpreter>,
+    ) -> io::Result<()> {
+        let arguments = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "setBreakpoints" => {
+                let lines: Vec<usize> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|bp| bp["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                self.breakpoints = lines.iter().copied().collect();
+                let breakpoints: Vec<Value> = lines
+                    .iter()
+                    .map(|line| json!({ "verified": true, "line": line }))
+                    .collect();
+                self.respond(request, json!({ "breakpoints": breakpoints }))
+            }
+            "setExceptionBreakpoints" => self.respond(request, json!({})),
+            "threads" => self.respond(
+                request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            ),
+            "stackTrace" | "scopes" | "variables" | "evaluate" => match interpreter {
+                Some(interpreter) => self.inspect(request, interpreter),
+                None => self.fail(request, "program is not paused"),
+            },
+            command => self.fail(request, &format!("unsupported request: {}", command)),
+        }
+    }
+
+    fn inspect(&mut self, request: &Value, interpreter: &mut Interpreter) -> io::Result<()> {
+        let arguments = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "stackTrace" => {
+                let frames = interpreter.frames();
+                let name = Path::new(&self.path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let source = json!({ "name": name, "path": self.path });
+                let mut stack = vec![json!({
+                    "id": 0,
+                    "name": frames.last().map_or("main", |frame| frame.name.as_str()),
+                    "line": self.stopped_at.0,
+                    "column": 1,
+                    "source": source,
+                })];
+                for (depth, index) in (0..frames.len()).rev().enumerate() {
+                    let caller = if index == 0 {
+                        "main"
+                    } else {
+                        frames[index - 1].name.as_str()
+                    };
+                    stack.push(json!({
+                        "id": depth + 1,
+                        "name": caller,
+                        "line": frames[index].call_line,
+                        "column": 1,
+                        "source": source,
+                    }));
+                }
+                let total = stack.len();
+                self.respond(
+                    request,
+                    json!({ "stackFrames": stack, "totalFrames": total }),
+                )
+            }
+            "scopes" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or_default() as usize;
+                let mut scopes = Vec::new();
+                for (depth, _) in local_scopes(interpreter, frame).iter().enumerate() {
+                    let name = if depth == 0 {
+                        "Locals".to_string()
+                    } else {
+                        format!("Enclosing ({})", depth)
+                    };
+                    scopes.push(json!({
+                        "name": name,
+                        "variablesReference": 1 + frame * SCOPES_PER_FRAME + depth,
+                        "expensive": false,
+                    }));
+                }
+                scopes.push(json!({
+                    "name": "Globals",
+                    "variablesReference": GLOBALS_REFERENCE,
+                    "expensive": false,
+                }));
+                self.respond(request, json!({ "scopes": scopes }))
+            }
+            "variables" => {
+                let reference =
+                    arguments["variablesReference"].as_u64().unwrap_or_default() as usize;
+                let scope = if reference == GLOBALS_REFERENCE {
+                    Some(Rc::clone(&interpreter.globals))
+                } else {
+                    // 0 means "no variables" in the protocol, nothing to list
+                    let Some(index) = reference.checked_sub(1) else {
+                        return self.fail(request, "invalid variables reference");
+                    };
+                    let frame = index / SCOPES_PER_FRAME;
+                    let depth = index % SCOPES_PER_FRAME;
+                    local_scopes(interpreter, frame).get(depth).cloned()
+                };
+                let variables: Vec<Value> = scope
+                    .map(|env| env.borrow().bindings())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, value)| {
+                        json!({
+                            "name": name,
+                            "value": interpreter.stringify(&value),
+                            "variablesReference": 0,
+                        })
+                    })
+                    .collect();
+                self.respond(request, json!({ "variables": variables }))
+            }
+            "evaluate" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or_default() as usize;
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                let (result, output) = evaluate(interpreter, frame, expression);
+                if !output.is_empty() {
+                    self.event("output", json!({ "category": "stdout", "output": output }))?;
+                }
+                match result {
+                    Ok(value) => {
+                        self.respond(request, json!({ "result": value, "variablesReference": 0 }))
+                    }
+                    Err(e) => self.fail(request, e.message()),
+                }
+            }
+            _ => unreachable!("only inspection requests are routed here"),
+        }
+    }
+
+    fn should_pause(&self, line: usize, depth: usize) -> Option<&'static str> {
+        let position = (line, depth);
+        let moved = position != self.stopped_at;
+        match self.mode {
+            StepMode::Entry => return Some("entry"),
+            StepMode::StepIn if moved => return Some("step"),
+            StepMode::StepOver(from) if depth <= from && moved => return Some("step"),
+            StepMode::StepOut(from) if depth < from => return Some("step"),
+            _ => {}
+        }
+        // only break once per line, not for every statement nested on it
+        if self.breakpoints.contains(&line) && self.last_position != Some(position) {
+            return Some("breakpoint");
+        }
+        None
+    }
+
+    fn pause(&mut self, interpreter: &mut Interpreter, reason: &str) -> Result<(), ReefError> {
+        let io_error = |e: io::Error| ReefError::Disconnected(format!("debug adapter: {}", e));
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )
+        .map_err(io_error)?;
+        let depth = interpreter.frames().len();
+        loop {
+            let Some(request) = read_message(&mut self.reader).map_err(io_error)? else {
+                self.disconnected = true;
+                return Err(ReefError::Disconnected("debugger disconnected".to_string()));
+            };
+            let mode = match request["command"].as_str().unwrap_or_default() {
+                "continue" => StepMode::Continue,
+                "next" => StepMode::StepOver(depth),
+                "stepIn" => StepMode::StepIn,
+                "stepOut" => StepMode::StepOut(depth),
+                "disconnect" | "terminate" => {
+                    self.respond(&request, json!({})).map_err(io_error)?;
+                    self.disconnected = true;
+                    return Err(ReefError::Disconnected("debugger disconnected".to_string()));
+                }
+                _ => {
+                    self.handle_common(&request, Some(interpreter))
+                        .map_err(io_error)?;
+                    continue;
+                }
+            };
+            self.mode = mode;
+            self.respond(&request, json!({ "allThreadsContinued": true }))
+                .map_err(io_error)?;
+            return Ok(());
+        }
+    }
+}
+
+// local scopes of a frame, innermost first. globals are reported separately
+fn local_scopes(interpreter: &Interpreter, frame: usize) -> Vec<EnvRef> {
+    let mut scopes = Vec::new();
+    let mut env = interpreter.frame_environment(frame);
+    while let Some(scope) = env {
+        if Rc::ptr_eq(&scope, &interpreter.globals) {
+            break;
+        }
+        env = scope.borrow().enclosing.clone();
+        scopes.push(scope);
+    }
+    scopes
+}
+
+fn evaluate(
+    interpreter: &mut Interpreter,
+    frame: usize,
+    expression: &str,
+) -> (Result<String, ReefError>, String) {
+    let tokens = Scanner::new(expression.to_string()).scan_tokens();
+    let expr = match Parser::new(tokens).parse_expression() {
+        Ok(expr) => expr,
+        Err(e) => return (Err(e), String::new()),
+    };
+    // captures `print` output from functions called while evaluating
+    let output = Capture::new();
+    let previous = interpreter.set_output(Box::new(output.clone()));
+    let result = interpreter.evaluate_in_frame(frame, &expr);
+    interpreter.set_output(previous);
+    let result = result.map(|value| interpreter.stringify(&value));
+    (result, output.take())
+}
+
+struct DapHook<R, W> {
+    session: Rc<RefCell<Session<R, W>>>,
+}
+
+impl<R: BufRead, W: Write> Debugger for DapHook<R, W> {
+    fn before_statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        stmt: &StmtKind,
+    ) -> Result<(), ReefError> {
+        // blocks are just containers, pause on what's inside them instead
+        if let StmtKind::Block { .. } = stmt {
+            return Ok(());
+        }
+        let Some(line) = stmt.line() else {
+            return Ok(());
+        };
+        let depth = interpreter.frames().len();
+        let mut session = self.session.borrow_mut();
+        let reason = session.should_pause(line, depth);
+        session.last_position = Some((line, depth));
+        match reason {
+            Some(reason) => {
+                session.stopped_at = (line, depth);
+                session.pause(interpreter, reason)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// forwards what the script prints to the client as output events
+struct OutputEvents<R, W> {
+    session: Rc<RefCell<Session<R, W>>>,
+}
+
+impl<R: BufRead, W: Write> Write for OutputEvents<R, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let output = String::from_utf8_lossy(buf);
+        // a broken pipe will surface on the next read, nothing to do here
+        let _ = self
+            .session
+            .borrow_mut()
+            .event("output", json!({ "category": "stdout", "output": output }));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,53 +1,195 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
-use crate::{Token, error::ReefError, expr::Value};
+use crate::{Token, error::ReefError, expr::Value, suggest::did_you_mean};
 
+/// a scope shared between the interpreter, nested blocks and every
+/// closure created inside it
+pub type EnvRef = Rc<RefCell<Environment>>;
+
+/// the global scope keeps its bindings by name. local scopes keep them in
+/// declaration order so resolved variables are a slot index away
 #[derive(Debug, Clone)]
 pub struct Environment {
-    values: HashMap<String, Value>,
-    pub enclosing: Option<Box<Environment>>,
+    values: HashMap<Rc<str>, Value>,
+    slots: Vec<Value>,
+    // name of each slot, for unresolved lookups and the debugger
+    names: Vec<Rc<str>>,
+    pub enclosing: Option<EnvRef>,
 }
 
 impl Environment {
-    pub fn new(enclosing: Option<Environment>) -> Self {
+    pub fn new(enclosing: Option<EnvRef>) -> Self {
         Environment {
-            enclosing: enclosing.map(Box::new),
+            enclosing,
             values: HashMap::new(),
+            slots: Vec::new(),
+            names: Vec::new(),
         }
     }
-    pub fn update_values(&mut self, name: String, value: Value) -> Result<Value, ReefError> {
+    pub fn new_ref(enclosing: Option<EnvRef>) -> EnvRef {
+        Rc::new(RefCell::new(Environment::new(enclosing)))
+    }
+    pub fn update_values(&mut self, name: Rc<str>, value: Value) -> Result<Value, ReefError> {
         self.values.insert(name, value.clone());
         Ok(value)
     }
 
-    pub fn define(&mut self, name: String, value: Value) -> Result<Value, ReefError> {
-        self.update_values(name, value)
+    pub fn define(&mut self, name: Rc<str>, value: Value) -> Result<Value, ReefError> {
+        if self.enclosing.is_none() {
+            return self.update_values(name, value);
+        }
+        self.slots.push(value.clone());
+        self.names.push(name);
+        Ok(value)
+    }
+
+    // the latest slot wins when a name is declared twice in one scope
+    fn slot_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().rposition(|local| **local == *name)
+    }
+
+    pub(crate) fn lookup(&self, name: &str) -> Option<Value> {
+        match self.slot_of(name) {
+            Some(slot) => Some(self.slots[slot].clone()),
+            None => self.values.get(name).cloned(),
+        }
+    }
+
+    fn replace(&mut self, name: &str, value: Value) -> bool {
+        match self.slot_of(name) {
+            Some(slot) => self.slots[slot] = value,
+            None => match self.values.get_mut(name) {
+                Some(current) => *current = value,
+                None => return false,
+            },
+        }
+        true
     }
 
     pub fn assign(&mut self, name: &Token, value: Value) -> Result<Value, ReefError> {
-        if self.values.contains_key(&name.lexeme) {
-            return self.update_values(name.lexeme.to_string(), value);
+        if self.replace(&name.lexeme, value.clone()) {
+            return Ok(value);
         }
-        if let Some(ref mut enc) = self.enclosing {
-            return enc.assign(name, value);
+        let mut env = self.enclosing.clone();
+        while let Some(current) = env {
+            let mut scope = current.borrow_mut();
+            if scope.replace(&name.lexeme, value.clone()) {
+                return Ok(value);
+            }
+            env = scope.enclosing.clone();
         }
-        Err(ReefError::reef_general_error(&format!(
-            "undefined variable: {:?}",
-            name
-        )))
+        Err(self.undefined_variable(name))
     }
 
     pub fn get(&self, name: &Token) -> Result<Value, ReefError> {
-        if let Some(val) = self.values.get(&name.lexeme) {
-            return Ok(val.clone());
+        if let Some(val) = self.lookup(&name.lexeme) {
+            return Ok(val);
+        }
+        let mut env = self.enclosing.clone();
+        while let Some(current) = env {
+            let scope = current.borrow();
+            if let Some(val) = scope.lookup(&name.lexeme) {
+                return Ok(val);
+            }
+            env = scope.enclosing.clone();
+        }
+        Err(self.undefined_variable(name))
+    }
+
+    fn ancestor(&self, depth: usize) -> Option<EnvRef> {
+        let mut env = self.enclosing.clone();
+        for _ in 1..depth {
+            env = env?.borrow().enclosing.clone();
         }
+        env
+    }
+
+    /// reads a resolved local, `depth` scopes up from this one
+    pub fn get_at(&self, depth: usize, slot: usize, name: &Token) -> Result<Value, ReefError> {
+        let value = if depth == 0 {
+            self.slots.get(slot).cloned()
+        } else {
+            self.ancestor(depth)
+                .and_then(|env| env.borrow().slots.get(slot).cloned())
+        };
+        value.ok_or_else(|| self.undefined_variable(name))
+    }
+
+    pub fn assign_at(
+        &mut self,
+        depth: usize,
+        slot: usize,
+        name: &Token,
+        value: Value,
+    ) -> Result<Value, ReefError> {
+        let assigned = if depth == 0 {
+            self.slots
+                .get_mut(slot)
+                .map(|current| *current = value.clone())
+        } else {
+            self.ancestor(depth).and_then(|env| {
+                env.borrow_mut()
+                    .slots
+                    .get_mut(slot)
+                    .map(|current| *current = value.clone())
+            })
+        };
+        match assigned {
+            Some(()) => Ok(value),
+            None => Err(self.undefined_variable(name)),
+        }
+    }
+
+    /// every value held directly by this scope
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Value> {
+        self.values.values().chain(&self.slots)
+    }
+
+    /// empties the scope, handing back what it held so the caller decides
+    /// when it gets dropped
+    pub(crate) fn clear(&mut self) -> (Vec<Value>, Option<EnvRef>) {
+        let mut values: Vec<Value> = self.values.drain().map(|(_, value)| value).collect();
+        values.append(&mut self.slots);
+        self.names.clear();
+        (values, self.enclosing.take())
+    }
+
+    /// bindings defined directly in this scope, sorted by name
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: BTreeMap<&str, &Value> = self
+            .values
+            .iter()
+            .map(|(name, value)| (&**name, value))
+            .collect();
+        // later slots shadow earlier ones with the same name
+        bindings.extend(self.names.iter().map(|name| &**name).zip(&self.slots));
+        bindings
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    /// every name visible from this scope, innermost first
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.names.iter().rev().map(|n| n.to_string()).collect();
+        names.extend(self.values.keys().map(|n| n.to_string()));
         if let Some(enc) = &self.enclosing {
-            return enc.get(name);
+            names.extend(enc.borrow().names());
         }
-        Err(ReefError::reef_general_error(&format!(
-            "undefined variable: '{}'",
-            name.lexeme
-        )))
+        names
+    }
+
+    fn undefined_variable(&self, name: &Token) -> ReefError {
+        let mut message = format!("undefined variable: '{}'", name.lexeme);
+        let names = self.names();
+        if let Some(suggestion) = did_you_mean(&name.lexeme, names.iter().map(String::as_str)) {
+            message.push_str(&format!(", did you mean '{}'?", suggestion));
+        }
+        ReefError::reef_line_runtime_error(name.line, &message)
     }
 }
 impl Default for Environment {
@@ -55,3 +197,56 @@ impl Default for Environment {
         Environment::new(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::Environment;
+    use crate::{Token, TokenType, error::ReefError, expr::Value};
+
+    fn identifier(name: &str) -> Token {
+        Token::new(TokenType::Identifier, name.to_string(), None, 1)
+    }
+
+    #[test]
+    fn test_undefined_variable_suggests_closest_name() {
+        let globals = Environment::new_ref(None);
+        globals
+            .borrow_mut()
+            .define("counter".into(), Value::Nil)
+            .unwrap();
+        let mut env = Environment::new(Some(globals));
+        env.define("total".into(), Value::Nil).unwrap();
+
+        let Err(ReefError::RuntimeError(message)) = env.get(&identifier("countr")) else {
+            panic!("expected an undefined variable error");
+        };
+        assert!(message.contains("did you mean 'counter'?"));
+
+        let Err(ReefError::RuntimeError(message)) = env.assign(&identifier("totl"), Value::Nil)
+        else {
+            panic!("expected an undefined variable error");
+        };
+        assert!(message.contains("did you mean 'total'?"));
+    }
+
+    #[test]
+    fn test_scopes_share_their_parent() {
+        let globals = Environment::new_ref(None);
+        globals
+            .borrow_mut()
+            .define("count".into(), Value::Number(0.0))
+            .unwrap();
+        let mut first = Environment::new(Some(Rc::clone(&globals)));
+        let second = Environment::new(Some(Rc::clone(&globals)));
+
+        first
+            .assign(&identifier("count"), Value::Number(1.0))
+            .unwrap();
+        let Ok(Value::Number(count)) = second.get(&identifier("count")) else {
+            panic!("expected count to be visible");
+        };
+        assert_eq!(count, 1.0);
+    }
+}
@@ -1,17 +1,49 @@
+use std::rc::Rc;
+
+use crate::expr::Value;
+use crate::func::ReefFunction;
+use crate::limits::Limit;
+use crate::output::diagnostic;
 use crate::token::{Token, TokenType};
 
 #[derive(Debug, Clone)]
 pub enum ReefError {
     ParseError(String),
     RuntimeError(String),
+    // not a real error: unwinds a `return` out of the function body
+    Return(Value),
+    // not a real error either: unwinds `return f(x);` so the calling
+    // function's frame is gone before f runs
+    TailCall(Rc<ReefFunction>, Vec<Value>),
+    // the script ran out of a budget the host gave it. unlike runtime
+    // errors, scripts can't recover from these
+    LimitExceeded(Limit),
+    // an error object on its way to a `catch`: something a script threw,
+    // or a runtime error raised inside a `try`. also holds the report
+    // printed when it was raised
+    Thrown(Value, String),
+    // the debugger went away while the script was paused. like limits,
+    // scripts can't catch it
+    Disconnected(String),
 }
 impl ReefError {
+    pub fn message(&self) -> &str {
+        match self {
+            ReefError::ParseError(message)
+            | ReefError::RuntimeError(message)
+            | ReefError::Thrown(_, message)
+            | ReefError::Disconnected(message) => message,
+            ReefError::Return(_) | ReefError::TailCall(..) => "can't return from top-level code",
+            ReefError::LimitExceeded(limit) => limit.message(),
+        }
+    }
     pub fn reef_error(line: usize, message: &str) -> ReefError {
         ReefError::reef_report(line, "", message)
     }
     pub fn reef_general_error(message: &str) -> ReefError {
-        eprintln!("Error: {}", message);
-        ReefError::ParseError(format!("Error: {}", message))
+        let message = format!("Error: {}", message);
+        diagnostic(&message);
+        ReefError::ParseError(message)
     }
     pub fn reef_error_at_line(token: &Token, message: &str) -> ReefError {
         let where_info = if token.token_type == TokenType::Eof {
@@ -23,13 +55,26 @@ impl ReefError {
     }
 
     pub fn reef_report(line: usize, where_info: &str, message: &str) -> ReefError {
-        eprintln!("[line {}] Error {}: {}", line, where_info, message);
-        ReefError::ParseError(format!("[line {}] Error {}: {}", line, where_info, message))
+        let message = format!("[line {}] Error {}: {}", line, where_info, message);
+        diagnostic(&message);
+        ReefError::ParseError(message)
     }
 
     pub fn reef_runtime_error(token: &Token, message: &str) -> ReefError {
-        eprintln!("Error {:?}: {}", token, message);
-        ReefError::RuntimeError(format!("Error {:?}: {}", token, message))
+        let message = format!("Error {:?}: {}", token, message);
+        diagnostic(&message);
+        ReefError::RuntimeError(message)
+    }
+
+    pub fn limit_exceeded(limit: Limit) -> ReefError {
+        diagnostic(&format!("Error: {}", limit));
+        ReefError::LimitExceeded(limit)
+    }
+
+    pub fn reef_line_runtime_error(line: usize, message: &str) -> ReefError {
+        let message = format!("[line {}] Error: {}", line, message);
+        diagnostic(&message);
+        ReefError::RuntimeError(message)
     }
 }
 
@@ -0,0 +1,65 @@
+use std::rc::Rc;
+
+use crate::{
+    error::ReefError,
+    expr::Value,
+    object::{HostObject, HostType},
+};
+
+/// what `catch` binds: a value a script threw, or a runtime error turned
+/// into one. scripts see it as an `Error` object
+#[derive(Debug)]
+pub struct Exception {
+    /// `Error` for thrown values, `RuntimeError` for errors the
+    /// interpreter raised
+    pub kind: Rc<str>,
+    pub message: Rc<str>,
+    /// calls in progress where it was raised, innermost first
+    pub trace: Vec<String>,
+    /// what was thrown, nil for runtime errors
+    pub value: Value,
+}
+
+impl Exception {
+    /// the exception inside an error object, if `value` is one
+    pub fn of(value: &Value) -> Option<&Exception> {
+        match value {
+            Value::Object(object) => object.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+fn exception(object: &HostObject) -> &Exception {
+    object
+        .downcast_ref()
+        .expect("error objects always wrap an exception")
+}
+
+/// the host type of error objects, one per interpreter
+pub(crate) fn error_type() -> Rc<HostType> {
+    let mut error = HostType::new("Error");
+    error
+        .property("message", |_, this| {
+            Ok(Value::String(Rc::clone(&exception(this).message)))
+        })
+        .property("kind", |_, this| {
+            Ok(Value::String(Rc::clone(&exception(this).kind)))
+        })
+        .property("trace", |_, this| {
+            Ok(Value::String(exception(this).trace.join("\n").into()))
+        })
+        .property("value", |_, this| Ok(exception(this).value.clone()))
+        .display(|this| {
+            let exception = exception(this);
+            format!("{}: {}", exception.kind, exception.message)
+        });
+    Rc::new(error)
+}
+
+/// whether `catch` can handle `error`. returns unwind past handlers, and
+/// running out of a budget the host set or losing the debugger has to
+/// stop the script
+pub(crate) fn is_catchable(error: &ReefError) -> bool {
+    matches!(error, ReefError::RuntimeError(_) | ReefError::Thrown(..))
+}
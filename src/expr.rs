@@ -2,16 +2,20 @@
 
 use crate::{
     Literal, Token, TokenType, error::ReefError, func::ReefCallable, interpreter::Interpreter,
+    module::Module, object::HostObject, stmt::StmtKind,
 };
 use std::{fmt, rc::Rc};
 
 #[derive(Debug, Clone)]
 pub enum Value {
-    String(String),
+    String(Rc<str>),
     Number(f64),
     Boolean(bool),
     Nil,
     Callable(Rc<dyn ReefCallable>),
+    // rust value the host handed to the script
+    Object(Rc<HostObject>),
+    Module(Rc<Module>),
 }
 
 impl Value {
@@ -42,6 +46,18 @@ impl Value {
             ))),
         }
     }
+    /// name of the value's type, for error messages
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Boolean(_) => "boolean",
+            Value::Nil => "nil",
+            Value::Callable(_) => "function",
+            Value::Object(object) => &object.kind().name,
+            Value::Module(_) => "module",
+        }
+    }
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Nil => false,
@@ -51,11 +67,22 @@ impl Value {
     }
 }
 
+/// where the resolver found a variable. locals are `depth` scopes up
+/// from the current one at `slot`; unresolved names (from the debugger
+/// or anything that skipped the resolver) are looked up by name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Unresolved,
+    Global,
+    Local { depth: usize, slot: usize },
+}
+
 #[derive(Debug, Clone)]
 pub enum ExprKind {
     Assign {
         name: Token,
         value: Box<ExprKind>,
+        binding: Binding,
     },
     Binary {
         left: Box<ExprKind>,
@@ -66,6 +93,17 @@ pub enum ExprKind {
         callee: Box<ExprKind>,
         token: Token,
         arguments: Vec<ExprKind>,
+        // `name: value` arguments, which come after the positional ones
+        named: Vec<(Token, ExprKind)>,
+    },
+    // `fun (a) { }` or `(a) => a`. name is an `anonymous` stand-in at the
+    // `fun` or `=>`, for stack traces and printing
+    Function {
+        name: Token,
+        parameters: Vec<Token>,
+        defaults: Vec<ExprKind>,
+        rest: Option<Token>,
+        body: Vec<StmtKind>,
     },
     Get {
         object: Box<ExprKind>,
@@ -76,6 +114,7 @@ pub enum ExprKind {
     },
     Literal {
         value: Literal,
+        line: usize,
     },
     Logical {
         left: Box<ExprKind>,
@@ -100,6 +139,29 @@ pub enum ExprKind {
     },
     Variable {
         name: Token,
+        binding: Binding,
     },
     None,
 }
+
+impl ExprKind {
+    /// line of the leftmost token in the expression
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ExprKind::Assign { name, .. } => Some(name.line),
+            ExprKind::Binary { left, operator, .. } | ExprKind::Logical { left, operator, .. } => {
+                left.line().or(Some(operator.line))
+            }
+            ExprKind::Call { callee, token, .. } => callee.line().or(Some(token.line)),
+            ExprKind::Get { object, name } | ExprKind::Set { object, name, .. } => {
+                object.line().or(Some(name.line))
+            }
+            ExprKind::Grouping { expression } => expression.line(),
+            ExprKind::Unary { operator, .. } => Some(operator.line),
+            ExprKind::Variable { name, .. } | ExprKind::Function { name, .. } => Some(name.line),
+            ExprKind::Super { keyword, .. } | ExprKind::This { keyword } => Some(keyword.line),
+            ExprKind::Literal { line, .. } => Some(*line),
+            ExprKind::None => None,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    Parser, Scanner, Token, TokenType,
+    error::ReefError,
+    token::{Trivia, TriviaKind},
+};
+
+const INDENT: &str = "    ";
+
+/// opinionated, token-driven pretty printer. works off the scanner's
+/// token stream plus trivia so comments and paragraph breaks survive
+pub struct Formatter<'a> {
+    tokens: &'a [Token],
+    trivia: &'a [Trivia],
+    out: String,
+    indent: usize,
+    paren_depth: usize,
+    pending_newline: bool,
+    pending_blank: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(tokens: &'a [Token], trivia: &'a [Trivia]) -> Self {
+        Formatter {
+            tokens,
+            trivia,
+            out: String::new(),
+            indent: 0,
+            paren_depth: 0,
+            pending_newline: false,
+            pending_blank: false,
+        }
+    }
+
+    /// formats reef source, refusing to touch anything that doesn't parse
+    pub fn format(source: &str) -> Result<String, ReefError> {
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+        Parser::new(tokens.clone()).parse()?;
+
+        let mut formatter = Formatter::new(&tokens, scanner.trivia());
+        formatter.run();
+        Ok(formatter.out)
+    }
+
+    fn run(&mut self) {
+        let mut trivia_idx = 0;
+        let mut i = 0;
+        while i < self.tokens.len() {
+            while trivia_idx < self.trivia.len() && self.trivia[trivia_idx].token_index == i {
+                self.emit_trivia(trivia_idx);
+                trivia_idx += 1;
+            }
+            let token = &self.tokens[i];
+            let prev = if i > 0 { self.tokens.get(i - 1) } else { None };
+            let next = self.tokens.get(i + 1);
+            let prev_prev = i.checked_sub(2).map(|j| &self.tokens[j]);
+
+            match token.token_type {
+                TokenType::Eof => break,
+                TokenType::LeftBrace => {
+                    self.write(&token.lexeme, prev.is_some());
+                    let next_is_close = next.is_some_and(|t| t.token_type == TokenType::RightBrace);
+                    let has_trivia = self
+                        .trivia
+                        .get(trivia_idx)
+                        .is_some_and(|t| t.token_index == i + 1);
+                    if next_is_close && !has_trivia {
+                        self.out.push('}');
+                        i += 1;
+                        self.after_close_brace(self.tokens.get(i + 1));
+                    } else {
+                        self.indent += 1;
+                        self.newline();
+                    }
+                }
+                TokenType::RightBrace => {
+                    self.indent = self.indent.saturating_sub(1);
+                    self.pending_blank = false;
+                    self.newline();
+                    self.write(&token.lexeme, false);
+                    self.after_close_brace(next);
+                }
+                TokenType::Semicolon => {
+                    self.write(&token.lexeme, false);
+                    if self.paren_depth == 0 {
+                        self.newline();
+                    }
+                }
+                TokenType::LeftParen => {
+                    let space = prev.is_some_and(|p| !is_callee_end(p) && !is_unary(p, prev_prev));
+                    self.write(&token.lexeme, space);
+                    self.paren_depth += 1;
+                }
+                TokenType::RightParen => {
+                    self.paren_depth = self.paren_depth.saturating_sub(1);
+                    self.write(&token.lexeme, false);
+                }
+                _ => {
+                    let space = needs_space(prev, token, prev_prev);
+                    self.write(&token.lexeme, space);
+                }
+            }
+            i += 1;
+        }
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+    }
+
+    fn after_close_brace(&mut self, next: Option<&Token>) {
+        match next.map(|t| t.token_type) {
+            Some(TokenType::Else)
+            | Some(TokenType::Catch)
+            | Some(TokenType::Finally)
+            | Some(TokenType::Semicolon)
+            | Some(TokenType::RightParen)
+            | Some(TokenType::Comma) => {}
+            _ => self.newline(),
+        }
+    }
+
+    fn emit_trivia(&mut self, idx: usize) {
+        let trivia = &self.trivia[idx];
+        match &trivia.kind {
+            TriviaKind::Comment(text) if trivia.trailing && !self.out.is_empty() => {
+                self.out.push(' ');
+                self.out.push_str(text);
+                self.newline();
+            }
+            TriviaKind::Comment(text) => {
+                if !self.out.is_empty() && !self.pending_newline {
+                    self.newline();
+                }
+                self.write(text, false);
+                self.newline();
+            }
+            TriviaKind::BlankLine => {
+                let after_open = self.out.ends_with('{');
+                if self.pending_newline && !after_open {
+                    self.pending_blank = true;
+                }
+            }
+        }
+    }
+
+    fn newline(&mut self) {
+        self.pending_newline = true;
+    }
+
+    fn write(&mut self, text: &str, space: bool) {
+        if self.pending_newline {
+            if !self.out.is_empty() {
+                self.out.push('\n');
+                if self.pending_blank {
+                    self.out.push('\n');
+                }
+            }
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+            self.pending_newline = false;
+            self.pending_blank = false;
+        } else if space {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+}
+
+// tokens that can end an operand, meaning a following '-' is binary
+fn is_operand_end(token: &Token) -> bool {
+    matches!(
+        token.token_type,
+        TokenType::Identifier
+            | TokenType::Number
+            | TokenType::String
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Nil
+            | TokenType::This
+            | TokenType::RightParen
+    )
+}
+
+// a '(' directly after one of these is a call or a function's parameter list
+fn is_callee_end(token: &Token) -> bool {
+    matches!(
+        token.token_type,
+        TokenType::Identifier | TokenType::RightParen | TokenType::This | TokenType::Super
+    )
+}
+
+fn is_unary(token: &Token, prev: Option<&Token>) -> bool {
+    match token.token_type {
+        TokenType::Bang => true,
+        TokenType::Minus => !prev.is_some_and(is_operand_end),
+        _ => false,
+    }
+}
+
+fn needs_space(prev: Option<&Token>, token: &Token, prev_prev: Option<&Token>) -> bool {
+    let Some(prev) = prev else {
+        return false;
+    };
+    if matches!(
+        token.token_type,
+        TokenType::Comma | TokenType::Semicolon | TokenType::Dot | TokenType::Colon
+    ) {
+        return false;
+    }
+    if matches!(
+        prev.token_type,
+        TokenType::LeftParen | TokenType::Dot | TokenType::Ellipsis
+    ) {
+        return false;
+    }
+    !is_unary(prev, prev_prev)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Formatter;
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let source = include_str!("../reef/hello.reef");
+        let once = Formatter::format(source).unwrap();
+        let twice = Formatter::format(&once).unwrap();
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn test_format_canonical_spacing() {
+        let source =
+            "var  a=1+-2 ;{print a*-(3-1);var b=!true;}\nif(a>1){print a;}else print \"no\";";
+        let expected = "var a = 1 + -2;\n{\n    print a * -(3 - 1);\n    var b = !true;\n}\nif (a > 1) {\n    print a;\n} else print \"no\";\n";
+        assert_eq!(Formatter::format(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_preserves_comments_and_blank_lines() {
+        let source = "// header\nvar a = 1; // trailing\n\n\n\n{\n\n  // inside\n  print a;\n\n}\n";
+        let expected = "// header\nvar a = 1; // trailing\n\n{\n    // inside\n    print a;\n}\n";
+        assert_eq!(Formatter::format(source).unwrap(), expected);
+    }
+}
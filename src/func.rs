@@ -1,35 +1,105 @@
-use crate::environment::Environment;
-use crate::expr::Value;
+use crate::environment::EnvRef;
+use crate::expr::{ExprKind, Value};
 use crate::stmt::StmtKind;
-use crate::{Literal, Token, TokenType, error::ReefError, interpreter::Interpreter};
-use std::{env, fmt};
+use crate::suggest::did_you_mean;
+use crate::{Token, error::ReefError, interpreter::Interpreter};
+use std::{any::Any, fmt, rc::Rc};
 
-pub type InterpreterFn = fn(&Interpreter, Vec<Value>) -> Result<Value, ReefError>;
+pub type InterpreterFn = fn(&mut Interpreter, Vec<Value>) -> Result<Value, ReefError>;
+
+/// what a host native is handed: the interpreter, so it can call back
+/// into reef, and the arguments. state goes in the closure's captures,
+/// behind a `Cell` or `RefCell` since a native can be reentered
+pub type HostFn = dyn Fn(&mut Interpreter, Vec<Value>) -> Result<Value, ReefError>;
+
+/// how many arguments a callable takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    // variadic, with this many required arguments first
+    AtLeast(usize),
+    // some arguments are optional
+    Between(usize, usize),
+}
+
+impl Arity {
+    /// arity of a reef function with these parameters
+    pub fn of_parameters(parameters: usize, defaults: usize, rest: bool) -> Self {
+        let required = parameters - defaults;
+        match (rest, defaults) {
+            (true, _) => Arity::AtLeast(required),
+            (false, 0) => Arity::Exactly(parameters),
+            (false, _) => Arity::Between(required, parameters),
+        }
+    }
+
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Arity::Exactly(arity) => count == arity,
+            Arity::AtLeast(arity) => count >= arity,
+            Arity::Between(min, max) => (min..=max).contains(&count),
+        }
+    }
+}
+
+impl From<usize> for Arity {
+    fn from(arity: usize) -> Self {
+        Arity::Exactly(arity)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exactly(arity) => write!(f, "{}", arity),
+            Arity::AtLeast(arity) => write!(f, "at least {}", arity),
+            Arity::Between(min, max) => write!(f, "{} to {}", min, max),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct FunctionDecl {
-    name: Token,
-    parameters: Vec<Token>,
-    body: Vec<StmtKind>,
+    pub name: Token,
+    pub parameters: Vec<Token>,
+    pub defaults: Vec<ExprKind>,
+    pub rest: Option<Token>,
+    pub body: Vec<StmtKind>,
 }
 
 impl FunctionDecl {
     fn from_statement(stmt: StmtKind) -> Result<Self, ReefError> {
-        match &stmt {
+        match stmt {
             StmtKind::Function {
                 name,
                 parameters,
+                defaults,
+                rest,
                 body,
             } => Ok(FunctionDecl {
-                name: name.clone(),
-                parameters: parameters.clone(),
-                body: body.clone(),
+                name,
+                parameters,
+                defaults,
+                rest,
+                body,
             }),
             _ => Err(ReefError::reef_general_error(
                 "expected stmtkind function for reef callable",
             )),
         }
     }
+
+    /// parameters every call has to fill
+    pub fn required(&self) -> usize {
+        self.parameters.len() - self.defaults.len()
+    }
+
+    /// default value of the parameter at `index`, if it has one
+    pub fn default(&self, index: usize) -> Option<&ExprKind> {
+        index
+            .checked_sub(self.required())
+            .and_then(|index| self.defaults.get(index))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,53 +110,178 @@ pub struct NativeFunction {
 
 #[derive(Debug, Clone)]
 pub struct ReefFunction {
-    pub declaration: FunctionDecl,
-    pub arity: usize,
-    pub func: InterpreterFn,
+    pub declaration: Rc<FunctionDecl>,
+    // scope the function was declared in
+    pub closure: EnvRef,
+    // top level scope of the module it was declared in, where its
+    // globals live
+    pub module: EnvRef,
 }
 
-pub trait ReefCallable: fmt::Debug {
+// `Any` lets a backend recover its own callable types from a `Value`
+pub trait ReefCallable: fmt::Debug + Any {
     fn arity(&self) -> usize;
-    fn call(&self, interpreter: &Interpreter, arguments: Vec<Value>) -> Result<Value, ReefError>;
+    /// argument counts the callable accepts, `arity` is the minimum
+    fn arity_spec(&self) -> Arity {
+        Arity::Exactly(self.arity())
+    }
+    fn name(&self) -> &str;
+    /// scope captured by the callable, traced by the garbage collector
+    fn closure(&self) -> Option<&EnvRef> {
+        None
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, ReefError>;
 }
 
 impl ReefCallable for NativeFunction {
     fn arity(&self) -> usize {
         self.arity
     }
-    fn call(&self, interpreter: &Interpreter, arguments: Vec<Value>) -> Result<Value, ReefError> {
+    fn name(&self) -> &str {
+        "<native fn>"
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, ReefError> {
+        (self.func)(interpreter, arguments)
+    }
+}
+
+/// native registered by the host, backed by a closure
+pub struct HostFunction {
+    pub name: Rc<str>,
+    pub arity: Arity,
+    pub func: Box<HostFn>,
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReefCallable for HostFunction {
+    fn arity(&self) -> usize {
+        match self.arity {
+            Arity::Exactly(arity) | Arity::AtLeast(arity) | Arity::Between(arity, _) => arity,
+        }
+    }
+    fn arity_spec(&self) -> Arity {
+        self.arity
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, ReefError> {
         (self.func)(interpreter, arguments)
     }
 }
 
 impl ReefFunction {
-    pub fn new(
-        declaration: StmtKind,
-        arity: usize,
-        func: InterpreterFn,
-    ) -> Result<Self, ReefError> {
+    pub fn new(declaration: StmtKind, closure: EnvRef, module: EnvRef) -> Result<Self, ReefError> {
         let declaration = FunctionDecl::from_statement(declaration)?;
         Ok(Self {
-            declaration,
-            arity,
-            func,
+            declaration: Rc::new(declaration),
+            closure,
+            module,
         })
     }
+
+    /// why a call with `count` positional arguments and the `named` ones
+    /// can't be made, naming the parameters at fault
+    pub fn check_arguments<'a>(
+        &self,
+        count: usize,
+        named: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), String> {
+        let declaration = &self.declaration;
+        let parameters = &declaration.parameters;
+        if declaration.rest.is_none() && count > parameters.len() {
+            let arity = self.arity_spec();
+            let plural = if arity == Arity::Exactly(1) { "" } else { "s" };
+            return Err(format!(
+                "{} takes {} argument{} but got {}",
+                self.name(),
+                arity,
+                plural,
+                count
+            ));
+        }
+        let mut filled = vec![false; parameters.len()];
+        filled
+            .iter_mut()
+            .take(count)
+            .for_each(|filled| *filled = true);
+        for name in named {
+            let Some(index) = parameters.iter().position(|p| *p.lexeme == *name) else {
+                let mut message = format!("{} has no parameter '{}'", self.name(), name);
+                let names = parameters.iter().map(|p| &*p.lexeme);
+                if let Some(suggestion) = did_you_mean(name, names) {
+                    message.push_str(&format!(", did you mean '{}'?", suggestion));
+                }
+                return Err(message);
+            };
+            if filled[index] {
+                return Err(format!("{} got two values for '{}'", self.name(), name));
+            }
+            filled[index] = true;
+        }
+        let missing: Vec<String> = (0..declaration.required())
+            .filter(|&index| !filled[index])
+            .map(|index| format!("'{}'", parameters[index].lexeme))
+            .collect();
+        match missing.len() {
+            0 => Ok(()),
+            1 => Err(format!(
+                "{} is missing argument {}",
+                self.name(),
+                missing[0]
+            )),
+            _ => Err(format!(
+                "{} is missing arguments {}",
+                self.name(),
+                missing.join(", ")
+            )),
+        }
+    }
 }
 
 impl ReefCallable for ReefFunction {
     fn arity(&self) -> usize {
-        self.arity
+        self.declaration.required()
     }
-    fn call(&self, interpreter: &Interpreter, arguments: Vec<Value>) -> Result<Value, ReefError> {
-        let decl = self.declaration;
-
-        let globals = Some(interpreter.globals.clone());
-        let environment = Environment::new(globals);
-        // for i in self.declaration.parameters.len() {
-        //     let param = decl.parameters.get(i);
-        //     environment.define()
-        // }
-        (self.func)(interpreter, arguments)
+    fn arity_spec(&self) -> Arity {
+        let declaration = &self.declaration;
+        Arity::of_parameters(
+            declaration.parameters.len(),
+            declaration.defaults.len(),
+            declaration.rest.is_some(),
+        )
+    }
+    fn name(&self) -> &str {
+        &self.declaration.name.lexeme
+    }
+    fn closure(&self) -> Option<&EnvRef> {
+        Some(&self.closure)
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, ReefError> {
+        interpreter.execute_function(self, arguments, Vec::new())
     }
 }
@@ -0,0 +1,319 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fmt,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    environment::{EnvRef, Environment},
+    expr::Value,
+    func::ReefCallable,
+    list::List,
+    map::Map,
+    module::Module,
+    object::HostObject,
+};
+
+// collections are due after this many allocations, or twice the live
+// count if that's higher
+const INITIAL_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GcStats {
+    pub collections: usize,
+    pub allocated: usize,
+    pub freed: usize,
+    pub live: usize,
+}
+
+impl fmt::Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gc: {} collections, {} environments allocated, {} freed, {} live",
+            self.collections, self.allocated, self.freed, self.live
+        )
+    }
+}
+
+/// cycle collector for environments, the only reef heap objects a
+/// script can change after creating them, so every cycle passes through
+/// one (a scope holding a closure that captured it, maybe by way of a
+/// list or map).
+///
+/// rather than enumerating roots it counts, for every tracked scope and
+/// everything reachable from one, how many strong references come from
+/// inside the heap. closures, lists, maps and modules are traced; other
+/// host objects and bound methods are opaque, so whatever they hold looks
+/// referenced from outside and a cycle through one is never freed.
+/// anything with references left over is held by the interpreter or a
+/// rust stack frame and is a root. whatever the roots can't reach is
+/// garbage, and emptying its scopes breaks its cycles
+pub struct Heap {
+    environments: Vec<Weak<RefCell<Environment>>>,
+    since_collection: usize,
+    threshold: usize,
+    stress: bool,
+    stats: GcStats,
+}
+
+// something on the heap the collector can see inside
+enum Node {
+    Environment(EnvRef),
+    Function(Rc<dyn ReefCallable>),
+    Object(Rc<HostObject>),
+    Module(Rc<Module>),
+}
+
+impl Node {
+    // heap objects a value refers to, if it can be part of a cycle
+    fn of(value: &Value) -> Option<Node> {
+        match value {
+            Value::Callable(callable) if callable.closure().is_some() => {
+                Some(Node::Function(Rc::clone(callable)))
+            }
+            Value::Object(object) => Some(Node::Object(Rc::clone(object))),
+            Value::Module(module) => Some(Node::Module(Rc::clone(module))),
+            _ => None,
+        }
+    }
+
+    fn address(&self) -> *const () {
+        match self {
+            Node::Environment(env) => Rc::as_ptr(env) as *const (),
+            Node::Function(function) => Rc::as_ptr(function) as *const (),
+            Node::Object(object) => Rc::as_ptr(object) as *const (),
+            Node::Module(module) => Rc::as_ptr(module) as *const (),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Environment(env) => Rc::strong_count(env),
+            Node::Function(function) => Rc::strong_count(function),
+            Node::Object(object) => Rc::strong_count(object),
+            Node::Module(module) => Rc::strong_count(module),
+        }
+    }
+
+    fn children(&self) -> Vec<Node> {
+        match self {
+            Node::Environment(env) => {
+                let env = env.borrow();
+                let parent = env.enclosing.iter().cloned().map(Node::Environment);
+                parent.chain(env.values().filter_map(Node::of)).collect()
+            }
+            Node::Function(function) => function
+                .closure()
+                .cloned()
+                .map(Node::Environment)
+                .into_iter()
+                .collect(),
+            Node::Object(object) => {
+                if let Some(list) = object.downcast_ref::<List>() {
+                    list.items.iter().filter_map(Node::of).collect()
+                } else if let Some(map) = object.downcast_ref::<Map>() {
+                    map.entries.values().filter_map(Node::of).collect()
+                } else {
+                    Vec::new()
+                }
+            }
+            Node::Module(module) => vec![Node::Environment(Rc::clone(module.scope()))],
+        }
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap {
+            environments: Vec::new(),
+            since_collection: 0,
+            threshold: INITIAL_THRESHOLD,
+            stress: false,
+            stats: GcStats::default(),
+        }
+    }
+
+    /// collect on every allocation, to shake out missing roots
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    pub fn stats(&self) -> GcStats {
+        GcStats {
+            live: self
+                .environments
+                .iter()
+                .filter(|env| env.strong_count() > 0)
+                .count(),
+            ..self.stats
+        }
+    }
+
+    /// starts tracking a new environment, returning whether a collection
+    /// is due
+    pub fn track(&mut self, env: &EnvRef) -> bool {
+        self.environments.push(Rc::downgrade(env));
+        self.stats.allocated += 1;
+        self.since_collection += 1;
+        self.stress || self.since_collection >= self.threshold
+    }
+
+    /// frees unreachable cycles, returning how many environments it emptied
+    pub fn collect(&mut self) -> usize {
+        let mut nodes: Vec<Node> = self
+            .environments
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(Node::Environment)
+            .collect();
+        let tracked = nodes.len();
+        let mut index: HashMap<*const (), usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.address(), i))
+            .collect();
+
+        // references from inside the heap, and the edges they form.
+        // scopes nobody tracks are left out, as if they weren't there
+        let mut refs = vec![0; nodes.len()];
+        let mut edges: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+        let mut next = 0;
+        while next < nodes.len() {
+            for child in nodes[next].children() {
+                let child = match index.get(&child.address()) {
+                    Some(&child) => child,
+                    None if matches!(child, Node::Environment(_)) => continue,
+                    None => {
+                        index.insert(child.address(), nodes.len());
+                        nodes.push(child);
+                        refs.push(0);
+                        edges.push(Vec::new());
+                        nodes.len() - 1
+                    }
+                };
+                refs[child] += 1;
+                edges[next].push(child);
+            }
+            next += 1;
+        }
+
+        // one strong reference each is ours, from building the list above
+        let mut marked = vec![false; nodes.len()];
+        let mut pending: VecDeque<usize> = (0..nodes.len())
+            .filter(|&i| nodes[i].strong_count() - 1 > refs[i])
+            .collect();
+        while let Some(i) = pending.pop_front() {
+            if !marked[i] {
+                marked[i] = true;
+                pending.extend(edges[i].iter().copied());
+            }
+        }
+
+        // emptying a scope drops closures that may hold the last reference
+        // to another one, so keep everything alive until the borrows end
+        let mut released = Vec::new();
+        let mut kept = Vec::new();
+        for (i, node) in nodes.iter().enumerate().take(tracked) {
+            let Node::Environment(env) = node else {
+                continue;
+            };
+            if marked[i] {
+                kept.push(Rc::downgrade(env));
+            } else {
+                released.push(env.borrow_mut().clear());
+            }
+        }
+        let freed = released.len();
+        drop(released);
+        drop(nodes);
+
+        self.environments = kept;
+        self.since_collection = 0;
+        self.threshold = INITIAL_THRESHOLD.max(self.environments.len() * 2);
+        self.stats.collections += 1;
+        self.stats.freed += freed;
+        freed
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Heap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::Heap;
+    use crate::environment::Environment;
+    use crate::expr::Value;
+    use crate::func::ReefFunction;
+    use crate::list::{List, list_type};
+    use crate::{Parser, Scanner};
+
+    #[test]
+    fn test_collects_closure_cycles_only() {
+        let mut heap = Heap::new();
+        let globals = Environment::new_ref(None);
+        heap.track(&globals);
+
+        let tokens = Scanner::new("fun f() {}".to_string()).scan_tokens();
+        let stmt = Parser::new(tokens).parse().unwrap().remove(0);
+
+        // a scope holding a closure over itself, unreachable from rust
+        let scope = Environment::new_ref(Some(Rc::clone(&globals)));
+        heap.track(&scope);
+        let function =
+            ReefFunction::new(stmt.clone(), Rc::clone(&scope), Rc::clone(&globals)).unwrap();
+        scope
+            .borrow_mut()
+            .define("f".into(), Value::Callable(Rc::new(function)))
+            .unwrap();
+        let weak = Rc::downgrade(&scope);
+        drop(scope);
+
+        // the same shape, but still referenced from here
+        let kept = Environment::new_ref(Some(Rc::clone(&globals)));
+        heap.track(&kept);
+        let function = ReefFunction::new(stmt, Rc::clone(&kept), Rc::clone(&globals)).unwrap();
+        kept.borrow_mut()
+            .define("f".into(), Value::Callable(Rc::new(function)))
+            .unwrap();
+
+        assert!(weak.upgrade().is_some());
+        assert_eq!(heap.collect(), 1);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(kept.borrow().bindings().len(), 1);
+        assert_eq!(heap.stats().live, 2);
+    }
+
+    #[test]
+    fn test_collects_cycles_through_lists() {
+        let mut heap = Heap::new();
+        let globals = Environment::new_ref(None);
+        heap.track(&globals);
+        let tokens = Scanner::new("fun f() {}".to_string()).scan_tokens();
+        let stmt = Parser::new(tokens).parse().unwrap().remove(0);
+
+        // scope -> list -> closure -> scope
+        let scope = Environment::new_ref(Some(Rc::clone(&globals)));
+        heap.track(&scope);
+        let function = ReefFunction::new(stmt, Rc::clone(&scope), Rc::clone(&globals)).unwrap();
+        let items = vec![Value::Callable(Rc::new(function))];
+        let list = list_type().instance(List { items });
+        scope.borrow_mut().define("l".into(), list.clone()).unwrap();
+        let weak = Rc::downgrade(&scope);
+        drop(scope);
+
+        // held from rust through the list, so the scope is still reachable
+        assert_eq!(heap.collect(), 0);
+        assert!(weak.upgrade().is_some());
+        drop(list);
+        assert_eq!(heap.collect(), 1);
+        assert!(weak.upgrade().is_none());
+    }
+}
@@ -0,0 +1,50 @@
+use std::{collections::HashSet, rc::Rc};
+
+/// hands out one shared allocation per distinct string, so interned names
+/// and literals clone for the cost of a refcount and usually compare
+/// equal by pointer
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: HashSet<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, text: &str) -> Rc<str> {
+        if let Some(existing) = self.strings.get(text) {
+            return Rc::clone(existing);
+        }
+        let interned: Rc<str> = Rc::from(text);
+        self.strings.insert(Rc::clone(&interned));
+        interned
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::Interner;
+
+    #[test]
+    fn test_intern_shares_allocations() {
+        let mut interner = Interner::new();
+        let a = interner.intern("count");
+        let b = interner.intern(&String::from("count"));
+        let c = interner.intern("other");
+        assert!(Rc::ptr_eq(&a, &b));
+        assert!(!Rc::ptr_eq(&a, &c));
+        assert_eq!(interner.len(), 2);
+    }
+}
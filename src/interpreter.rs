@@ -1,17 +1,44 @@
 use std::{
+    any::Any,
+    collections::HashMap,
+    io::{self, Write},
+    mem,
     rc::Rc,
-    time::{SystemTime, UNIX_EPOCH},
+    sync::{Arc, atomic::AtomicBool},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
-use crate::func::{NativeFunction, ReefCallable};
+use crate::convert::TypedFn;
+use crate::func::{Arity, FunctionDecl, HostFunction, NativeFunction, ReefCallable, ReefFunction};
 use crate::{
-    Literal, Token, TokenType,
-    environment::Environment,
+    Literal, Parser, Scanner, Token, TokenType,
+    environment::{EnvRef, Environment},
     error::ReefError,
-    expr::{ExprKind, Value},
+    exception::{self, Exception},
+    expr::{Binding, ExprKind, Value},
+    gc::{GcStats, Heap},
+    interner::Interner,
+    limits::Limits,
+    list::{self, List},
+    module::{FileLoader, Module, ModuleLoader},
+    object::HostType,
+    output::{self, Capture},
+    resolver::Resolver,
     stmt::StmtKind,
+    vm::machine::Machine,
 };
 
+/// calls that can be in progress at once before a script fails with
+/// "stack overflow". every reef call nests a few rust calls, so keep this
+/// well within the stack of the thread running the interpreter
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+// frames shown at each end of a stack trace, the middle is elided
+const TRACE_EDGE: usize = 10;
+
+// globals the interpreter defines before any script runs
+pub const NATIVE_GLOBALS: &[&str] = &["clock", "gc"];
+
 fn check_number_operand(operator: &Token, right_operand: &Value) -> Result<(), ReefError> {
     match right_operand {
         Value::Number(_) => Ok(()),
@@ -35,11 +62,44 @@ fn check_number_operands(
         )),
     }
 }
-fn is_equal(a: &Value, b: &Value) -> bool {
+/// keeps both ends of a long stack trace
+pub(crate) fn elide_trace(lines: Vec<String>) -> Vec<String> {
+    if lines.len() <= TRACE_EDGE * 2 {
+        return lines;
+    }
+    let omitted = lines.len() - TRACE_EDGE * 2;
+    let mut trace = lines[..TRACE_EDGE].to_vec();
+    trace.push(format!("... {} more frames ...", omitted));
+    trace.extend_from_slice(&lines[lines.len() - TRACE_EDGE..]);
+    trace
+}
+
+pub(crate) fn stringify(value: &Value) -> String {
+    match value {
+        Value::Number(n) => n.to_string(),
+        Value::Boolean(n) => n.to_string(),
+        Value::String(n) => n.to_string(),
+        Value::Nil => String::from("nil"),
+        Value::Callable(callable) => format!("<fn {}>", callable.name()),
+        Value::Object(object) => object.display(),
+        Value::Module(module) => format!("<module {}>", module.name),
+    }
+}
+
+// the reef function behind a callable, if it is one
+fn reef_function(callable: &Rc<dyn ReefCallable>) -> Option<Rc<ReefFunction>> {
+    let any: Rc<dyn Any> = Rc::clone(callable) as Rc<dyn Any>;
+    any.downcast::<ReefFunction>().ok()
+}
+
+pub(crate) fn is_equal(a: &Value, b: &Value) -> bool {
     match (a, b) {
         (Value::Number(l), Value::Number(r)) => l == r,
-        (Value::String(l), Value::String(r)) => l == r,
+        // interned strings share an allocation, so most equal pairs stop at the pointer
+        (Value::String(l), Value::String(r)) => Rc::ptr_eq(l, r) || l == r,
         (Value::Boolean(l), Value::Boolean(r)) => l == r,
+        (Value::Object(l), Value::Object(r)) => l.equals(r),
+        (Value::Module(l), Value::Module(r)) => Rc::ptr_eq(l, r),
         (Value::Nil, Value::Nil) => true,
         (_, Value::Nil) => false,
         (Value::Nil, _) => false,
@@ -47,14 +107,74 @@ fn is_equal(a: &Value, b: &Value) -> bool {
     }
 }
 
+/// hook run before every statement, used by `reef dap` to pause and
+/// inspect a running script
+pub trait Debugger {
+    fn before_statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        stmt: &StmtKind,
+    ) -> Result<(), ReefError>;
+}
+
+#[derive(Debug)]
+pub struct CallFrame {
+    pub name: String,
+    // line of the call expression in the calling frame
+    pub call_line: usize,
+    // scope the call was made from
+    caller_environment: EnvRef,
+    // `try` statements running in this call. a `return f(x);` inside one
+    // can't be a tail call, its handlers have to see f run
+    tries: usize,
+}
+
 pub struct Interpreter {
-    pub globals: Environment,
-    environment: Environment,
+    pub globals: EnvRef,
+    // natives and globals the host defined, all that modules see of the
+    // importer's globals
+    builtins: EnvRef,
+    // top level scope of the module running now, the globals for the
+    // main script
+    module: EnvRef,
+    environment: EnvRef,
+    frames: Vec<CallFrame>,
+    debugger: Option<Box<dyn Debugger>>,
+    interner: Interner,
+    heap: Heap,
+    max_call_depth: usize,
+    limits: Limits,
+    // where `print` writes, stdout unless the host says otherwise
+    output: Box<dyn Write>,
+    // where error reports go while this interpreter runs, stderr if None
+    pub(crate) diagnostics: Option<Box<dyn Write>>,
+    loader: Box<dyn ModuleLoader>,
+    // modules that finished running, by name
+    modules: HashMap<String, Rc<Module>>,
+    // modules being run, importers first
+    importing: Vec<String>,
+    // path of the main script, which its imports are relative to
+    script: Option<String>,
+    // type of the objects `catch` binds
+    error_type: Rc<HostType>,
+    // type of the lists rest parameters gather
+    list_type: Rc<HostType>,
+    // `try` bodies with a `catch` running now. while there are any,
+    // runtime errors become error objects as they leave a call, before
+    // its frame and the stack trace are gone
+    catching: usize,
+    // the vm running now, lent out while one of its natives runs so
+    // closures they call back can continue on it
+    pub(crate) vm: Option<Machine>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut globals = Environment::new(None);
+        let mut heap = Heap::new();
+        let globals = Environment::new_ref(None);
+        heap.track(&globals);
+        let builtins = Environment::new_ref(None);
+        heap.track(&builtins);
         let clock = NativeFunction {
             arity: 0,
             func: |_interpreter, _args| {
@@ -65,22 +185,236 @@ impl Interpreter {
                 Ok(Value::Number(time))
             },
         };
+        let gc = NativeFunction {
+            arity: 0,
+            func: |interpreter, _args| Ok(Value::Number(interpreter.collect_garbage() as f64)),
+        };
 
-        globals.define("clock".to_string(), Value::Callable(Rc::new(clock)));
+        let natives: [(&str, Value); 2] = [
+            ("clock", Value::Callable(Rc::new(clock))),
+            ("gc", Value::Callable(Rc::new(gc))),
+        ];
+        for (name, value) in natives {
+            for scope in [&globals, &builtins] {
+                scope
+                    .borrow_mut()
+                    .define(name.into(), value.clone())
+                    .expect("defining a global can't fail");
+            }
+        }
 
         Interpreter {
-            environment: globals.clone(),
+            environment: Rc::clone(&globals),
+            module: Rc::clone(&globals),
             globals,
+            builtins,
+            frames: Vec::new(),
+            debugger: None,
+            interner: Interner::new(),
+            heap,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            limits: Limits::new(),
+            output: Box::new(io::stdout()),
+            diagnostics: None,
+            loader: Box::new(FileLoader::default()),
+            modules: HashMap::new(),
+            importing: Vec::new(),
+            script: None,
+            error_type: exception::error_type(),
+            list_type: list::list_type(),
+            catching: 0,
+            vm: None,
         }
     }
-    pub fn stringify(&self, value: &Value) -> String {
-        match value {
-            Value::Number(n) => n.to_string(),
-            Value::Boolean(n) => n.to_string(),
-            Value::String(n) => n.to_string(),
-            Value::Nil => String::from("nil"),
-            _ => todo!(),
+
+    /// sends `print` output to `sink`, returning the previous sink
+    pub fn set_output(&mut self, sink: Box<dyn Write>) -> Box<dyn Write> {
+        mem::replace(&mut self.output, sink)
+    }
+
+    /// sends error reports raised while this interpreter runs to `sink`
+    /// instead of stderr
+    pub fn set_diagnostics(&mut self, sink: Box<dyn Write>) {
+        self.diagnostics = Some(sink);
+    }
+
+    /// writes a line of program output
+    pub fn write_output(&mut self, text: &str) {
+        // one write per line, so sinks that forward writes as messages
+        // see whole lines. a closed stdout isn't the script's problem
+        let _ = self.output.write_all(format!("{}\n", text).as_bytes());
+    }
+
+    // runs with this interpreter's diagnostics sink installed
+    fn reporting<T>(&mut self, run: impl FnOnce(&mut Self) -> T) -> T {
+        let sink = self.diagnostics.take();
+        let (result, sink) = output::with_diagnostics(sink, || run(self));
+        self.diagnostics = sink;
+        result
+    }
+
+    /// evaluates source like `eval`, returning what it printed alongside
+    /// the result
+    pub fn eval_captured(&mut self, source: &str) -> (String, Result<Value, ReefError>) {
+        let capture = Capture::new();
+        let previous = self.set_output(Box::new(capture.clone()));
+        let result = self.eval(source);
+        self.set_output(previous);
+        (capture.take(), result)
+    }
+
+    /// defines a global visible to every script run afterwards, replacing
+    /// any earlier value
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        let name = self.interner.intern(name);
+        for scope in [&self.globals, &self.builtins] {
+            scope
+                .borrow_mut()
+                .define(Rc::clone(&name), value.clone())
+                .expect("defining a global can't fail");
+        }
+    }
+
+    /// exposes a rust closure to scripts as the global function `name`
+    pub fn register_fn<F>(&mut self, name: &str, arity: impl Into<Arity>, func: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Value>) -> Result<Value, ReefError> + 'static,
+    {
+        let function = HostFunction {
+            name: name.into(),
+            arity: arity.into(),
+            func: Box::new(func),
+        };
+        self.define_global(name, Value::Callable(Rc::new(function)));
+    }
+
+    /// exposes an ordinary rust function as the global function `name`,
+    /// converting its arguments and result. a wrong argument count or type
+    /// is a runtime error naming the function
+    pub fn register_typed<Args, F: TypedFn<Args>>(&mut self, name: &str, func: F) {
+        let owner: Rc<str> = name.into();
+        self.register_fn(name, F::ARITY, move |interpreter, arguments| {
+            func.call_typed(arguments).unwrap_or_else(|message| {
+                Err(interpreter.host_error(&format!("{}: {}", owner, message)))
+            })
+        });
+    }
+
+    // line of the call into the native running now, if any
+    fn host_line(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.call_line)
+    }
+
+    /// runtime error raised by a native, reported at the line calling it
+    pub fn host_error(&self, message: &str) -> ReefError {
+        ReefError::reef_line_runtime_error(self.host_line(), message)
+    }
+
+    /// calls a reef value from rust, typically from inside a native
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> Result<Value, ReefError> {
+        let token = Token::new(TokenType::Identifier, "<host>", None, self.host_line());
+        self.call_value(callee.clone(), &token, arguments)
+    }
+
+    /// stops the script after this many statements and expressions
+    pub fn set_step_budget(&mut self, budget: Option<u64>) {
+        self.limits.set_step_budget(budget);
+    }
+
+    /// stops the script once the clock passes `deadline`
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.limits.set_deadline(deadline);
+    }
+
+    /// caps the bytes of scopes, functions and strings a script keeps alive
+    pub fn set_memory_limit(&mut self, bytes: Option<usize>) {
+        self.limits.set_memory_limit(bytes);
+    }
+
+    /// set from any thread to stop the running script
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        self.limits.interrupt_handle()
+    }
+
+    /// clears an interrupt, which otherwise stops every later script too
+    pub fn reset_interrupt(&mut self) {
+        self.limits.reset_interrupt();
+    }
+
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// calls in progress, innermost first, with long traces cut down to
+    /// both ends
+    pub fn stack_trace(&self) -> Vec<String> {
+        let lines: Vec<String> = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| format!("in {}, called from line {}", frame.name, frame.call_line))
+            .collect();
+        elide_trace(lines)
+    }
+
+    /// frees scopes that only keep each other alive, returning how many
+    pub fn collect_garbage(&mut self) -> usize {
+        self.heap.collect()
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    /// collects on every scope allocation
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.heap.set_stress(stress);
+    }
+
+    // every scope goes through here so the collector can find cycles
+    fn new_environment(&mut self, enclosing: EnvRef) -> Result<EnvRef, ReefError> {
+        let environment = Environment::new_ref(Some(enclosing));
+        self.limits
+            .charge(&environment, mem::size_of::<Environment>())?;
+        if self.heap.track(&environment) {
+            self.heap.collect();
+        }
+        Ok(environment)
+    }
+
+    pub fn set_debugger(&mut self, debugger: Box<dyn Debugger>) {
+        self.debugger = Some(debugger);
+    }
+
+    pub fn clear_debugger(&mut self) -> Option<Box<dyn Debugger>> {
+        self.debugger.take()
+    }
+
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    /// scope a frame is executing in, counted from the innermost (0)
+    pub fn frame_environment(&self, frame: usize) -> Option<EnvRef> {
+        if frame == 0 {
+            return Some(Rc::clone(&self.environment));
         }
+        let index = self.frames.len().checked_sub(frame)?;
+        Some(Rc::clone(&self.frames[index].caller_environment))
+    }
+
+    /// evaluates an expression as if it appeared in the given frame
+    pub fn evaluate_in_frame(&mut self, frame: usize, expr: &ExprKind) -> Result<Value, ReefError> {
+        let Some(target) = self.frame_environment(frame) else {
+            return Err(ReefError::reef_general_error("no such frame"));
+        };
+        let current = std::mem::replace(&mut self.environment, target);
+        let result = self.evaluate(expr);
+        self.environment = current;
+        result
+    }
+    pub fn stringify(&self, value: &Value) -> String {
+        stringify(value)
     }
 
     fn evaluate_binary(
@@ -98,7 +432,8 @@ impl Interpreter {
                     Ok(Value::Number(addition_result))
                 }
                 (Value::String(l), Value::String(r)) => {
-                    let concat_result = format!("{}{}", l, r);
+                    let concat_result: Rc<str> = format!("{}{}", l, r).into();
+                    self.limits.charge(&concat_result, concat_result.len())?;
                     Ok(Value::String(concat_result))
                 }
 
@@ -201,13 +536,29 @@ impl Interpreter {
         })
     }
 
-    fn evaluate_assignment(&mut self, name: &Token, value: &ExprKind) -> Result<Value, ReefError> {
+    fn evaluate_assignment(
+        &mut self,
+        name: &Token,
+        value: &ExprKind,
+        binding: Binding,
+    ) -> Result<Value, ReefError> {
         let value = self.evaluate(value)?;
-        self.environment.assign(name, value)
+        match binding {
+            Binding::Local { depth, slot } => self
+                .environment
+                .borrow_mut()
+                .assign_at(depth, slot, name, value),
+            Binding::Global => self.module.borrow_mut().assign(name, value),
+            Binding::Unresolved => self.environment.borrow_mut().assign(name, value),
+        }
     }
 
-    fn evaluate_variable(&self, name: &Token) -> Result<Value, ReefError> {
-        self.environment.get(name)
+    fn evaluate_variable(&self, name: &Token, binding: Binding) -> Result<Value, ReefError> {
+        match binding {
+            Binding::Local { depth, slot } => self.environment.borrow().get_at(depth, slot, name),
+            Binding::Global => self.module.borrow().get(name),
+            Binding::Unresolved => self.environment.borrow().get(name),
+        }
     }
 
     fn evaluate_logical(
@@ -235,8 +586,13 @@ impl Interpreter {
     }
 
     pub fn evaluate(&mut self, expr: &ExprKind) -> Result<Value, ReefError> {
+        self.limits.step()?;
         match expr {
-            ExprKind::Assign { name, value } => self.evaluate_assignment(name, value),
+            ExprKind::Assign {
+                name,
+                value,
+                binding,
+            } => self.evaluate_assignment(name, value, *binding),
             ExprKind::Binary {
                 left,
                 operator,
@@ -246,32 +602,100 @@ impl Interpreter {
                 callee,
                 token,
                 arguments,
-            } => self.evaluate_call_expr(callee, token, arguments),
-            // ExprKind::Get { object, name } => {}
+                named,
+            } => self.evaluate_call_expr(callee, token, arguments, named),
+            ExprKind::Get { object, name } => {
+                let object = self.evaluate(object)?;
+                self.get_property(object, &name.lexeme)
+                    .unwrap_or_else(|message| Err(ReefError::reef_runtime_error(name, &message)))
+            }
+            ExprKind::Function {
+                name,
+                parameters,
+                defaults,
+                rest,
+                body,
+            } => {
+                let declaration = StmtKind::Function {
+                    name: name.clone(),
+                    parameters: parameters.clone(),
+                    defaults: defaults.clone(),
+                    rest: rest.clone(),
+                    body: body.clone(),
+                };
+                let function = ReefFunction::new(
+                    declaration,
+                    Rc::clone(&self.environment),
+                    Rc::clone(&self.module),
+                )?;
+                let function = Rc::new(function);
+                self.limits
+                    .charge(&function, mem::size_of::<ReefFunction>())?;
+                Ok(Value::Callable(function))
+            }
             ExprKind::Grouping { expression } => self.evaluate(expression),
-            ExprKind::Literal { value } => self.evaluate_literal(value),
+            ExprKind::Literal { value, .. } => self.evaluate_literal(value),
             ExprKind::Logical {
                 left,
                 operator,
                 right,
             } => self.evaluate_logical(left, operator, right),
-            // ExprKind::Set {
-            //     object,
-            //     name,
-            //     value,
-            // } => {}
+            ExprKind::Set {
+                object,
+                name,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+                let value = self.evaluate(value)?;
+                self.set_property(object, &name.lexeme, value)
+                    .unwrap_or_else(|message| Err(ReefError::reef_runtime_error(name, &message)))
+            }
             // ExprKind::Super { keyword, method } => {}
             // ExprKind::This { keyword } => {}
             ExprKind::Unary { operator, right } => self.evaluate_unary(operator, right),
-            ExprKind::Variable { name } => self.evaluate_variable(name),
+            ExprKind::Variable { name, binding } => self.evaluate_variable(name, *binding),
             _ => todo!(),
         }
     }
+    // the outer error says what's wrong, the caller adds where
+    pub(crate) fn get_property(
+        &mut self,
+        object: Value,
+        name: &str,
+    ) -> Result<Result<Value, ReefError>, String> {
+        match object {
+            Value::Object(object) => object.get(self, name),
+            Value::Module(module) => module.export(name).map(Ok),
+            other => Err(format!(
+                "can't read property '{}' of a {}",
+                name,
+                other.type_name()
+            )),
+        }
+    }
+
+    pub(crate) fn set_property(
+        &mut self,
+        object: Value,
+        name: &str,
+        value: Value,
+    ) -> Result<Result<Value, ReefError>, String> {
+        match object {
+            Value::Object(object) => Ok(object.set(self, name, value.clone())?.map(|()| value)),
+            other => Err(format!(
+                "can't set property '{}' on a {}",
+                name,
+                other.type_name()
+            )),
+        }
+    }
+
     fn evaluate_call_expr(
         &mut self,
         callee: &ExprKind,
         token: &Token,
         arguments: &Vec<ExprKind>,
+        named: &[(Token, ExprKind)],
     ) -> Result<Value, ReefError> {
         let callee_val = self.evaluate(callee)?;
         let mut arguments_val: Vec<Value> = Vec::new();
@@ -279,17 +703,67 @@ impl Interpreter {
             let expr = self.evaluate(arg)?;
             arguments_val.push(expr);
         }
+        if named.is_empty() {
+            return self.call_value(callee_val, token, arguments_val);
+        }
+        let mut named_val = Vec::new();
+        for (name, arg) in named {
+            named_val.push((Rc::clone(&name.lexeme), self.evaluate(arg)?));
+        }
+        self.call_named(callee_val, token, arguments_val, named_val)
+    }
+
+    // only reef functions know their parameter names
+    fn call_named(
+        &mut self,
+        callee_val: Value,
+        token: &Token,
+        arguments_val: Vec<Value>,
+        named: Vec<(Rc<str>, Value)>,
+    ) -> Result<Value, ReefError> {
+        let Value::Callable(callable) = callee_val else {
+            return Err(ReefError::reef_runtime_error(
+                token,
+                "can only call funcs and classes",
+            ));
+        };
+        let Some(function) = reef_function(&callable) else {
+            let message = format!("{} doesn't take named arguments", callable.name());
+            return Err(ReefError::reef_runtime_error(token, &message));
+        };
+        function
+            .check_arguments(arguments_val.len(), named.iter().map(|(name, _)| &**name))
+            .map_err(|message| ReefError::reef_runtime_error(token, &message))?;
+        self.in_frame(function.name(), token, |interpreter| {
+            interpreter.execute_function(&function, arguments_val, named)
+        })
+    }
+
+    fn call_value(
+        &mut self,
+        callee_val: Value,
+        token: &Token,
+        arguments_val: Vec<Value>,
+    ) -> Result<Value, ReefError> {
         match callee_val {
             Value::Callable(callable) => {
-                let expected_len = callable.arity();
+                let expected_len = callable.arity_spec();
                 let actual_len = arguments_val.len();
-                if expected_len != actual_len {
-                    ReefError::reef_runtime_error(
-                        token,
-                        &format!("Expected: {} args, got {} args", expected_len, actual_len),
-                    );
-                };
-                callable.call(self, arguments_val)
+                if !expected_len.accepts(actual_len) {
+                    let message = match reef_function(&callable) {
+                        Some(function) => function
+                            .check_arguments(actual_len, [])
+                            .err()
+                            .unwrap_or_default(),
+                        None => {
+                            format!("Expected: {} args, got {} args", expected_len, actual_len)
+                        }
+                    };
+                    return Err(ReefError::reef_runtime_error(token, &message));
+                }
+                self.in_frame(callable.name(), token, |interpreter| {
+                    callable.call(interpreter, arguments_val)
+                })
             }
             _ => Err(ReefError::reef_runtime_error(
                 token,
@@ -297,6 +771,176 @@ impl Interpreter {
             )),
         }
     }
+    // runs `call` with a frame for it on the stack
+    fn in_frame(
+        &mut self,
+        name: &str,
+        token: &Token,
+        call: impl FnOnce(&mut Self) -> Result<Value, ReefError>,
+    ) -> Result<Value, ReefError> {
+        if self.frames.len() >= self.max_call_depth {
+            let trace = self.stack_trace().join("\n  ");
+            return Err(ReefError::reef_line_runtime_error(
+                token.line,
+                &format!("stack overflow\n  {}", trace),
+            ));
+        }
+        self.frames.push(CallFrame {
+            name: name.to_string(),
+            call_line: token.line,
+            caller_environment: Rc::clone(&self.environment),
+            tries: 0,
+        });
+        let mut result = call(self);
+        if self.catching > 0 {
+            result = result.map_err(|error| self.caught(error));
+        }
+        self.frames.pop();
+        result
+    }
+
+    /// runs a reef function body in a fresh scope on top of the scope the
+    /// function was declared in, seeing the globals of its module. tail calls made by the body run here too,
+    /// in place of the function that made them, so they don't grow the
+    /// rust stack
+    pub(crate) fn execute_function(
+        &mut self,
+        function: &ReefFunction,
+        arguments: Vec<Value>,
+        named: Vec<(Rc<str>, Value)>,
+    ) -> Result<Value, ReefError> {
+        let declaration = Rc::clone(&function.declaration);
+        let closure = Rc::clone(&function.closure);
+        // most calls stay inside one module
+        if Rc::ptr_eq(&self.module, &function.module) {
+            return self.run_function(declaration, closure, arguments, named);
+        }
+        let module = mem::replace(&mut self.module, Rc::clone(&function.module));
+        let result = self.run_function(declaration, closure, arguments, named);
+        self.module = module;
+        result
+    }
+
+    fn run_function(
+        &mut self,
+        mut declaration: Rc<FunctionDecl>,
+        mut closure: EnvRef,
+        mut arguments: Vec<Value>,
+        mut named: Vec<(Rc<str>, Value)>,
+    ) -> Result<Value, ReefError> {
+        loop {
+            let environment = self.new_environment(closure)?;
+            self.bind_arguments(&declaration, &environment, arguments, mem::take(&mut named))?;
+            match self.execute_block(&declaration.body, environment) {
+                Ok(()) => return Ok(Value::Nil),
+                Err(ReefError::Return(value)) => return Ok(value),
+                Err(ReefError::TailCall(function, args)) => {
+                    if let Some(frame) = self.frames.last_mut() {
+                        frame.name = function.name().to_string();
+                    }
+                    // the caller's module has to be put back afterwards, so
+                    // calls into another module don't reuse the frame
+                    if !Rc::ptr_eq(&self.module, &function.module) {
+                        return self.execute_function(&function, args, Vec::new());
+                    }
+                    declaration = Rc::clone(&function.declaration);
+                    closure = Rc::clone(&function.closure);
+                    arguments = args;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // defines the parameters in a call's scope, in order, so a default can
+    // use the parameters before it. the call has been checked already
+    fn bind_arguments(
+        &mut self,
+        declaration: &FunctionDecl,
+        environment: &EnvRef,
+        arguments: Vec<Value>,
+        mut named: Vec<(Rc<str>, Value)>,
+    ) -> Result<(), ReefError> {
+        let mut arguments = arguments.into_iter();
+        for (index, param) in declaration.parameters.iter().enumerate() {
+            let value = match arguments.next() {
+                Some(value) => value,
+                None => match named.iter().position(|(name, _)| *name == param.lexeme) {
+                    Some(found) => named.swap_remove(found).1,
+                    None => match declaration.default(index) {
+                        Some(default) => {
+                            let previous =
+                                mem::replace(&mut self.environment, Rc::clone(environment));
+                            let value = self.evaluate(default);
+                            self.environment = previous;
+                            value?
+                        }
+                        None => Value::Nil,
+                    },
+                },
+            };
+            environment
+                .borrow_mut()
+                .define(param.lexeme.clone(), value)?;
+        }
+        if let Some(rest) = &declaration.rest {
+            let items: Vec<Value> = arguments.collect();
+            let bytes = items.len() * mem::size_of::<Value>();
+            let list = self.list_type.instance(List { items });
+            if let Value::Object(object) = &list {
+                self.limits.charge(object, bytes)?;
+            }
+            environment.borrow_mut().define(rest.lexeme.clone(), list)?;
+        }
+        Ok(())
+    }
+
+    // `return f(x);` inside a function. a reef function taking that many
+    // arguments unwinds to the caller's `execute_function` to run there,
+    // anything else is called here as usual
+    fn tail_call(
+        &mut self,
+        callee: &ExprKind,
+        token: &Token,
+        arguments: &[ExprKind],
+    ) -> Result<ReefError, ReefError> {
+        let callee_val = self.evaluate(callee)?;
+        let mut arguments_val = Vec::new();
+        for arg in arguments {
+            arguments_val.push(self.evaluate(arg)?);
+        }
+        if let Value::Callable(callable) = &callee_val
+            && let Some(function) = reef_function(callable)
+            && function.arity_spec().accepts(arguments_val.len())
+        {
+            return Ok(ReefError::TailCall(function, arguments_val));
+        }
+        let value = self.call_value(callee_val, token, arguments_val)?;
+        Ok(ReefError::Return(value))
+    }
+
+    fn execute_import(
+        &mut self,
+        keyword: &Token,
+        path: &str,
+        alias: Option<&Token>,
+        names: &[Token],
+    ) -> Result<(), ReefError> {
+        let module = self.import_module(path, keyword.line)?;
+        let mut environment = self.environment.borrow_mut();
+        if let Some(alias) = alias {
+            environment.define(alias.lexeme.clone(), Value::Module(module))?;
+            return Ok(());
+        }
+        for name in names {
+            let value = module
+                .export(&name.lexeme)
+                .map_err(|message| ReefError::reef_runtime_error(name, &message))?;
+            environment.define(name.lexeme.clone(), value)?;
+        }
+        Ok(())
+    }
+
     fn execute_expression(&mut self, expr: &ExprKind) -> Result<(), ReefError> {
         self.evaluate(expr)?;
         Ok(())
@@ -304,10 +948,111 @@ impl Interpreter {
 
     fn execute_print(&mut self, expr: &ExprKind) -> Result<(), ReefError> {
         let value = self.evaluate(expr)?;
-        println!("{}", self.stringify(&value));
+        let text = self.stringify(&value);
+        self.write_output(&text);
         Ok(())
     }
 
+    fn execute_try(
+        &mut self,
+        body: &[StmtKind],
+        catch: Option<&(Token, Vec<StmtKind>)>,
+        finally: Option<&[StmtKind]>,
+    ) -> Result<(), ReefError> {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.tries += 1;
+        }
+        let result = match catch {
+            Some((name, handler)) => self.execute_try_catch(body, name, handler),
+            None => self
+                .new_environment(Rc::clone(&self.environment))
+                .and_then(|environment| self.execute_block(body, environment)),
+        };
+        if let Some(frame) = self.frames.last_mut() {
+            frame.tries -= 1;
+        }
+        // a script out of budget or without its debugger has to stop, not
+        // get a chance to return
+        if let Err(ReefError::LimitExceeded(_) | ReefError::Disconnected(_)) = result {
+            return result;
+        }
+        if let Some(finally) = finally {
+            // runs whether the body returned, threw or finished. a return or
+            // error of its own replaces whatever was on its way out
+            let environment = self.new_environment(Rc::clone(&self.environment))?;
+            self.execute_block(finally, environment)?;
+        }
+        result
+    }
+
+    fn execute_try_catch(
+        &mut self,
+        body: &[StmtKind],
+        name: &Token,
+        handler: &[StmtKind],
+    ) -> Result<(), ReefError> {
+        let environment = self.new_environment(Rc::clone(&self.environment))?;
+        self.catching += 1;
+        // errors report as they're raised, which is only right if nothing
+        // catches them
+        let (result, held) = output::hold_diagnostics(|| self.execute_block(body, environment));
+        self.catching -= 1;
+        match result {
+            Err(error) if exception::is_catchable(&error) => {
+                let ReefError::Thrown(error, _) = self.caught(error) else {
+                    unreachable!("catchable errors become error objects");
+                };
+                let environment = self.new_environment(Rc::clone(&self.environment))?;
+                environment
+                    .borrow_mut()
+                    .define(name.lexeme.clone(), error)?;
+                self.execute_block(handler, environment)
+            }
+            result => {
+                for line in held.lines() {
+                    output::diagnostic(line);
+                }
+                result
+            }
+        }
+    }
+
+    // `throw value;`. error objects are thrown as they are, so rethrowing
+    // keeps where they came from, anything else is wrapped in a new one
+    fn throw(&mut self, line: usize, value: Value) -> ReefError {
+        let error = match Exception::of(&value) {
+            Some(_) => value,
+            None => {
+                let message = self.stringify(&value);
+                self.exception("Error", &message, value)
+            }
+        };
+        let message = Exception::of(&error).map_or("", |exception| &exception.message);
+        let report = format!("[line {}] Error: {}", line, message);
+        output::diagnostic(&report);
+        ReefError::Thrown(error, report)
+    }
+
+    // a runtime error as an error object, with the calls in progress now
+    fn caught(&mut self, error: ReefError) -> ReefError {
+        match error {
+            ReefError::RuntimeError(report) => {
+                let error = self.exception("RuntimeError", &report, Value::Nil);
+                ReefError::Thrown(error, report)
+            }
+            error => error,
+        }
+    }
+
+    fn exception(&self, kind: &str, message: &str, value: Value) -> Value {
+        self.error_type.instance(Exception {
+            kind: kind.into(),
+            message: message.into(),
+            trace: self.stack_trace(),
+            value,
+        })
+    }
+
     fn execute_var(&mut self, name: &Token, initializer: &ExprKind) -> Result<(), ReefError> {
         let mut value = Value::Nil;
         match initializer {
@@ -317,32 +1062,27 @@ impl Interpreter {
             }
         }
         self.environment
-            .define(name.lexeme.clone(), value.clone())?;
+            .borrow_mut()
+            .define(name.lexeme.clone(), value)?;
         Ok(())
     }
 
     fn execute_block(
         &mut self,
-        statements: &Vec<StmtKind>,
-        environment: Environment,
+        statements: &[StmtKind],
+        environment: EnvRef,
     ) -> Result<(), ReefError> {
         let previous = std::mem::replace(&mut self.environment, environment);
 
         let result = (|| {
             for stmt in statements {
-                // propagating error instead
-                // of having a `finally` kind of clause
-                // to revert to previous env
-                // check here if there are recovery issues
                 self.execute(stmt)?;
             }
             Ok(())
         })();
 
-        if let Some(parent) = self.environment.enclosing.take() {
-            self.environment = *parent;
-        }
-
+        // restored on errors and returns too
+        self.environment = previous;
         result
     }
 
@@ -366,13 +1106,18 @@ impl Interpreter {
         Ok(())
     }
     pub fn execute(&mut self, stmt: &StmtKind) -> Result<(), ReefError> {
+        self.limits.step()?;
+        if let Some(mut debugger) = self.debugger.take() {
+            let resumed = debugger.before_statement(self, stmt);
+            self.debugger = Some(debugger);
+            resumed?;
+        }
         match stmt {
             StmtKind::Expression { expr } => self.execute_expression(expr)?,
             StmtKind::Print { expr } => self.execute_print(expr)?,
             StmtKind::Var { name, initializer } => self.execute_var(name, initializer)?,
             StmtKind::Block { statements } => {
-                let parent = std::mem::take(&mut self.environment);
-                let new_env = Environment::new(Some(parent));
+                let new_env = self.new_environment(Rc::clone(&self.environment))?;
                 self.execute_block(statements, new_env)?
             }
             StmtKind::If {
@@ -381,23 +1126,171 @@ impl Interpreter {
                 else_branch,
             } => self.execute_if(condition, then_branch, else_branch)?,
             StmtKind::While { condition, body } => self.execute_while(condition, body)?,
-            StmtKind::Function {
-                name,
-                parameters,
-                body,
-            } => {
-                dbg!(name, parameters, body);
+            StmtKind::Function { name, .. } => {
+                let function = ReefFunction::new(
+                    stmt.clone(),
+                    Rc::clone(&self.environment),
+                    Rc::clone(&self.module),
+                )?;
+                let function = Rc::new(function);
+                self.limits
+                    .charge(&function, mem::size_of::<ReefFunction>())?;
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme.clone(), Value::Callable(function))?;
+            }
+            StmtKind::Import {
+                keyword,
+                path,
+                alias,
+                names,
+            } => self.execute_import(keyword, path, alias.as_ref(), names)?,
+            StmtKind::Return { value, .. } => {
+                let value = match value {
+                    ExprKind::None => Value::Nil,
+                    ExprKind::Call {
+                        callee,
+                        token,
+                        arguments,
+                        named,
+                    } if named.is_empty()
+                        && self.frames.last().is_some_and(|frame| frame.tries == 0) =>
+                    {
+                        return Err(self.tail_call(callee, token, arguments)?);
+                    }
+                    _ => self.evaluate(value)?,
+                };
+                return Err(ReefError::Return(value));
             }
+            StmtKind::Throw { keyword, value } => {
+                let value = self.evaluate(value)?;
+                return Err(self.throw(keyword.line, value));
+            }
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => self.execute_try(body, catch.as_ref(), finally.as_deref())?,
             _ => todo!(),
         };
         Ok(())
     }
 
-    pub fn interpret(&mut self, stmts: Vec<StmtKind>) -> Result<(), ReefError> {
-        for stmt in stmts {
-            self.execute(&stmt)?
+    pub fn interpret(&mut self, mut stmts: Vec<StmtKind>) -> Result<(), ReefError> {
+        Resolver::new(&mut self.interner).resolve(&mut stmts);
+        self.reporting(|interpreter| {
+            for stmt in stmts {
+                interpreter.execute(&stmt)?
+            }
+            Ok(())
+        })
+    }
+
+    /// runs source against this interpreter's globals, so definitions stay
+    /// around for later calls. the value of a trailing expression
+    /// statement is returned, otherwise nil
+    pub fn eval(&mut self, source: &str) -> Result<Value, ReefError> {
+        self.reporting(|interpreter| interpreter.eval_source(source))
+    }
+
+    fn eval_source(&mut self, source: &str) -> Result<Value, ReefError> {
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+        if let Some((_, _, error)) = scanner.errors().first() {
+            return Err(error.clone());
         }
-        Ok(())
+        let mut stmts = Parser::new(tokens).parse()?;
+        Resolver::new(&mut self.interner).resolve(&mut stmts);
+        let result = match stmts.last() {
+            Some(StmtKind::Expression { .. }) => stmts.pop(),
+            _ => None,
+        };
+        for stmt in &stmts {
+            self.execute(stmt)?;
+        }
+        match result {
+            Some(StmtKind::Expression { expr }) => self.evaluate(&expr),
+            _ => Ok(Value::Nil),
+        }
+    }
+
+    /// where `import` finds modules, files relative to the importer
+    /// unless the host says otherwise
+    pub fn set_module_loader(&mut self, loader: Box<dyn ModuleLoader>) {
+        self.loader = loader;
+    }
+
+    /// runs the module `path` names, unless it already ran, and hands back
+    /// what it defined
+    pub fn import(&mut self, path: &str) -> Result<Rc<Module>, ReefError> {
+        let line = self.host_line();
+        self.reporting(|interpreter| interpreter.import_module(path, line))
+    }
+
+    /// names the file the main script came from, so its imports resolve
+    /// next to it
+    pub fn set_script_path(&mut self, path: &str) {
+        self.script = Some(path.to_string());
+    }
+
+    pub(crate) fn import_module(
+        &mut self,
+        path: &str,
+        line: usize,
+    ) -> Result<Rc<Module>, ReefError> {
+        let importer = self.importing.last().or(self.script.as_ref());
+        let importer = importer.map(String::as_str);
+        let name = self
+            .loader
+            .resolve(path, importer)
+            .map_err(|message| ReefError::reef_line_runtime_error(line, &message))?;
+        if let Some(module) = self.modules.get(&name) {
+            return Ok(Rc::clone(module));
+        }
+        if let Some(start) = self.importing.iter().position(|running| *running == name) {
+            let cycle: Vec<&str> = self.importing[start..]
+                .iter()
+                .chain([&name])
+                .map(String::as_str)
+                .collect();
+            let message = format!("import cycle: {}", cycle.join(" -> "));
+            return Err(ReefError::reef_line_runtime_error(line, &message));
+        }
+        let source = self
+            .loader
+            .load(&name)
+            .map_err(|message| ReefError::reef_line_runtime_error(line, &message))?;
+
+        self.importing.push(name.clone());
+        let scope = self.run_module(&source);
+        self.importing.pop();
+        let module = Rc::new(Module::new(name.clone(), scope?));
+        self.modules.insert(name, Rc::clone(&module));
+        Ok(module)
+    }
+
+    // a module's top level runs in a scope of its own, which its functions
+    // keep using as their globals. past it are only the builtins, never
+    // the importer's variables
+    fn run_module(&mut self, source: &str) -> Result<EnvRef, ReefError> {
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+        if let Some((_, _, error)) = scanner.errors().first() {
+            return Err(error.clone());
+        }
+        let mut stmts = Parser::new(tokens).parse()?;
+        Resolver::new(&mut self.interner).resolve(&mut stmts);
+        let scope = self.new_environment(Rc::clone(&self.builtins))?;
+        let module = mem::replace(&mut self.module, Rc::clone(&scope));
+        let result = self.execute_block(&stmts, Rc::clone(&scope));
+        self.module = module;
+        result.map(|()| scope)
+    }
+
+    /// current value of a global, if the name is defined
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().lookup(name)
     }
 }
 impl Default for Interpreter {
@@ -405,3 +1298,430 @@ impl Default for Interpreter {
         Interpreter::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant};
+
+    use super::{Interpreter, is_equal};
+    use crate::func::Arity;
+    use crate::output::Capture;
+    use crate::{Parser, Scanner, Token, TokenType, error::ReefError, expr::Value, limits::Limit};
+
+    fn run(interpreter: &mut Interpreter, source: &str) {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        interpreter.interpret(stmts).unwrap();
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Value {
+        let token = Token::new(TokenType::Identifier, name.to_string(), None, 1);
+        interpreter.globals.borrow().get(&token).unwrap()
+    }
+
+    #[test]
+    fn test_closures_share_captured_scope() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun counter() { var n = 0; fun next() { n = n + 1; return n; } return next; }\n\
+             var a = counter(); var b = counter();\n\
+             a(); a(); b();\n\
+             var result = a() * 10 + b();",
+        );
+        let Value::Number(result) = global(&interpreter, "result") else {
+            panic!("expected a number");
+        };
+        assert_eq!(result, 32.0);
+    }
+
+    #[test]
+    fn test_equal_literals_are_interned() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "var a = \"reef\"; var b = \"reef\"; var c = \"re\" + \"ef\";",
+        );
+        let (Value::String(a), Value::String(b), Value::String(c)) = (
+            global(&interpreter, "a"),
+            global(&interpreter, "b"),
+            global(&interpreter, "c"),
+        ) else {
+            panic!("expected strings");
+        };
+        assert!(Rc::ptr_eq(&a, &b));
+        assert!(!Rc::ptr_eq(&a, &c));
+        assert!(is_equal(&Value::String(a), &Value::String(c)));
+    }
+
+    #[test]
+    fn test_globals_are_shared_with_scripts() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "var x = 1; { x = x + 1; var y = 5; }");
+        let Value::Number(x) = global(&interpreter, "x") else {
+            panic!("expected a number");
+        };
+        assert_eq!(x, 2.0);
+        let token = Token::new(TokenType::Identifier, "y".to_string(), None, 1);
+        assert!(interpreter.globals.borrow().get(&token).is_err());
+    }
+
+    #[test]
+    fn test_gc_frees_closure_cycles() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun make() { var n = 0; fun inc() { n = n + 1; } }\n\
+             for (var i = 0; i < 100; i = i + 1) make();\n\
+             var freed = gc();",
+        );
+        let Value::Number(freed) = global(&interpreter, "freed") else {
+            panic!("expected a number");
+        };
+        assert!(freed >= 100.0);
+        assert!(interpreter.gc_stats().live < 10);
+    }
+
+    #[test]
+    fn test_tail_calls_run_in_constant_stack() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun count(n) { if (n == 0) return \"done\"; return count(n - 1); }\n\
+             var result = count(1000000);",
+        );
+        let Value::String(result) = global(&interpreter, "result") else {
+            panic!("expected a string");
+        };
+        assert_eq!(&*result, "done");
+        assert!(interpreter.frames().is_empty());
+    }
+
+    #[test]
+    fn test_call_depth_limit_reports_trace() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_call_depth(50);
+        let source = "fun f(n) { return 1 + f(n + 1); }\nf(0);";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let error = interpreter.interpret(stmts).unwrap_err();
+        let lines: Vec<&str> = error.message().lines().collect();
+        assert_eq!(lines[0], "[line 1] Error: stack overflow");
+        assert_eq!(lines[1].trim(), "in f, called from line 1");
+        assert_eq!(lines[11].trim(), "... 30 more frames ...");
+        assert_eq!(lines.len(), 22);
+        assert!(interpreter.frames().is_empty());
+    }
+
+    #[test]
+    fn test_limits_stop_runaway_scripts() {
+        let parse = |source: &str| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens();
+            Parser::new(tokens).parse().unwrap()
+        };
+        let exceeded = |result: Result<(), ReefError>| match result {
+            Err(ReefError::LimitExceeded(limit)) => Some(limit),
+            _ => None,
+        };
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_step_budget(Some(10_000));
+        let result = interpreter.interpret(parse("while (true) {}"));
+        assert_eq!(exceeded(result), Some(Limit::Steps));
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_memory_limit(Some(1 << 16));
+        let result = interpreter.interpret(parse("var s = \"ab\"; while (true) s = s + s;"));
+        assert_eq!(exceeded(result), Some(Limit::Memory));
+
+        // garbage doesn't count against the cap
+        let mut interpreter = Interpreter::new();
+        interpreter.set_memory_limit(Some(1 << 16));
+        let source = "var i = 0; while (i < 10000) { var s = \"abcd\" + \"efgh\"; i = i + 1; }";
+        assert!(interpreter.interpret(parse(source)).is_ok());
+
+        // a new budget applies to a reused interpreter
+        let mut interpreter = Interpreter::new();
+        interpreter.set_step_budget(Some(10_000));
+        assert!(interpreter.interpret(parse("while (true) {}")).is_err());
+        interpreter.set_step_budget(Some(100));
+        assert!(interpreter.interpret(parse("print 1;")).is_ok());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_deadline(Some(Instant::now() + Duration::from_millis(20)));
+        let result = interpreter.interpret(parse("while (true) {}"));
+        assert_eq!(exceeded(result), Some(Limit::Deadline));
+
+        let mut interpreter = Interpreter::new();
+        let interrupt = interpreter.interrupt_handle();
+        let host = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            interrupt.store(true, Ordering::Relaxed);
+        });
+        let result = interpreter.interpret(parse("while (true) {}"));
+        host.join().unwrap();
+        assert_eq!(exceeded(result), Some(Limit::Interrupted));
+    }
+
+    #[test]
+    fn test_host_closures() {
+        let mut interpreter = Interpreter::new();
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        interpreter.register_fn("tick", 0, move |_, _| {
+            counter.set(counter.get() + 1);
+            Ok(Value::Number(counter.get() as f64))
+        });
+        interpreter.register_fn("sum", Arity::AtLeast(1), |_, args| {
+            let mut total = 0.0;
+            for arg in args {
+                total += arg.as_number()?;
+            }
+            Ok(Value::Number(total))
+        });
+        // calls back into reef with the function it's given
+        interpreter.register_fn("twice", 2, |interpreter, mut args| {
+            let x = args.pop().unwrap();
+            let once = interpreter.call(&args[0], vec![x])?;
+            interpreter.call(&args[0], vec![once])
+        });
+        run(
+            &mut interpreter,
+            "tick(); tick();\n\
+             var total = sum(1, 2, 3, 4);\n\
+             fun double(n) { return n * 2 + tick() - tick() + 1; }\n\
+             var quad = twice(double, 5);",
+        );
+        assert_eq!(calls.get(), 6);
+        let Value::Number(total) = global(&interpreter, "total") else {
+            panic!("expected a number");
+        };
+        assert_eq!(total, 10.0);
+        let Value::Number(quad) = global(&interpreter, "quad") else {
+            panic!("expected a number");
+        };
+        assert_eq!(quad, 20.0);
+
+        // a call with the wrong count fails before the closure runs
+        let result = interpreter.eval("tick(1);");
+        assert!(matches!(result, Err(ReefError::RuntimeError(_))));
+        assert_eq!(calls.get(), 6);
+    }
+
+    #[test]
+    fn test_typed_natives() {
+        fn longer_than(limit: f64, text: String) -> bool {
+            text.len() as f64 > limit
+        }
+        let mut interpreter = Interpreter::new();
+        interpreter.register_typed("longer_than", longer_than);
+        interpreter.register_typed("shout", |text: String, times: Option<u32>| {
+            text.to_uppercase().repeat(times.unwrap_or(1) as usize)
+        });
+        run(
+            &mut interpreter,
+            "var long = longer_than(3, \"reef\");\nvar loud = shout(\"ab\", 2);",
+        );
+        assert!(matches!(global(&interpreter, "long"), Value::Boolean(true)));
+        let Value::String(loud) = global(&interpreter, "loud") else {
+            panic!("expected a string");
+        };
+        assert_eq!(&*loud, "ABAB");
+
+        let mut error = |source: &str| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens();
+            let stmts = Parser::new(tokens).parse().unwrap();
+            interpreter
+                .interpret(stmts)
+                .unwrap_err()
+                .message()
+                .to_string()
+        };
+        assert_eq!(
+            error("longer_than(\"3\", \"reef\");"),
+            "[line 1] Error: longer_than: argument 1: expected number, got string"
+        );
+        assert_eq!(
+            error("\nshout(\"a\", 2.5);"),
+            "[line 2] Error: shout: argument 2: expected u32, got 2.5"
+        );
+        assert!(error("shout();").ends_with("Expected: 2 args, got 0 args"));
+    }
+
+    #[test]
+    fn test_host_calls_into_persistent_scripts() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_global("greeting", Value::String("hello".into()));
+        interpreter
+            .eval(
+                "var calls = 0;\n\
+                 fun greet(name) { calls = calls + 1; return greeting + \", \" + name; }",
+            )
+            .unwrap();
+
+        let greet = interpreter.get_global("greet").unwrap();
+        let Value::String(hello) = interpreter
+            .call(&greet, vec![Value::String("reef".into())])
+            .unwrap()
+        else {
+            panic!("expected a string");
+        };
+        assert_eq!(&*hello, "hello, reef");
+
+        // a failed eval leaves earlier definitions alone
+        assert!(interpreter.eval("calls = calls + missing;").is_err());
+        interpreter.define_global("greeting", Value::String("hi".into()));
+        interpreter.eval("greet(\"again\");").unwrap();
+        let Value::Number(calls) = interpreter.eval("calls;").unwrap() else {
+            panic!("expected a number");
+        };
+        assert_eq!(calls, 2.0);
+        assert!(interpreter.get_global("missing").is_none());
+    }
+
+    #[test]
+    fn test_output_and_diagnostics_sinks() {
+        let mut interpreter = Interpreter::new();
+        let (printed, result) = interpreter.eval_captured("print 1 + 2;\nprint \"reef\";");
+        assert!(result.is_ok());
+        assert_eq!(printed, "3\nreef\n");
+
+        let diagnostics = Capture::new();
+        interpreter.set_diagnostics(Box::new(diagnostics.clone()));
+        let (printed, result) = interpreter.eval_captured("print \"before\";\nprint -nil;");
+        assert!(result.is_err());
+        assert_eq!(printed, "before\n");
+        assert!(diagnostics.take().contains("must be a number"));
+    }
+
+    #[test]
+    fn test_function_expressions_close_over_their_scope() {
+        let mut interpreter = Interpreter::new();
+        let (printed, result) = interpreter.eval_captured(
+            "var adder = fun (a) { return (b) => a + b; };\n\
+             var add3 = adder(3);\n\
+             print add3(4);\n\
+             print adder(10)(5);\n\
+             print add3;",
+        );
+        assert!(result.is_ok());
+        assert_eq!(printed, "7\n15\n<fn anonymous>\n");
+    }
+
+    #[test]
+    fn test_default_rest_and_named_arguments() {
+        let mut interpreter = Interpreter::new();
+        let (printed, result) = interpreter.eval_captured(
+            "fun greet(name, greeting = \"hello\", twice = greeting + greeting) {\n\
+                 return twice + \" \" + name;\n\
+             }\n\
+             print greet(\"reef\");\n\
+             print greet(greeting: \"hi\", name: \"you\");\n\
+             fun gather(first, ...rest) { return rest; }\n\
+             print gather(1, 2, 3);\n\
+             print gather(1).length;",
+        );
+        assert!(result.is_ok());
+        assert_eq!(printed, "hellohello reef\nhihi you\n[2, 3]\n0\n");
+
+        let mut error = |source: &str| match interpreter.eval(source) {
+            Err(ReefError::RuntimeError(message)) => message,
+            other => panic!("expected a runtime error, got {:?}", other),
+        };
+        assert!(error("fun f(a, b, c = 1) {} f();").ends_with("f is missing arguments 'a', 'b'"));
+        assert!(
+            error("fun f(a, b = 1) {} f(1, 2, 3);").ends_with("f takes 1 to 2 arguments but got 3")
+        );
+        assert!(
+            error("fun f(name) {} f(nmae: 1);")
+                .ends_with("f has no parameter 'nmae', did you mean 'name'?")
+        );
+        assert!(error("fun f(a) {} f(1, a: 2);").ends_with("f got two values for 'a'"));
+    }
+
+    #[test]
+    fn test_wrong_argument_counts_stop_native_calls() {
+        let mut interpreter = Interpreter::new();
+        let (printed, result) = interpreter.eval_captured("clock(1, 2); print \"ran on\";");
+        assert_eq!(printed, "");
+        match result {
+            Err(ReefError::RuntimeError(message)) => {
+                assert!(message.ends_with("Expected: 0 args, got 2 args"))
+            }
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+        // get indexes its arguments, running it with none would panic
+        match interpreter.eval("fun gather(...rest) { return rest.get(); } gather(1);") {
+            Err(ReefError::RuntimeError(message)) => {
+                assert!(message.ends_with("Expected: 1 args, got 0 args"))
+            }
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_try_catch_finally() {
+        let mut interpreter = Interpreter::new();
+        let diagnostics = Capture::new();
+        interpreter.set_diagnostics(Box::new(diagnostics.clone()));
+        let (printed, result) = interpreter.eval_captured(
+            "fun negate(x) { return -x; }\n\
+             fun first() { try { return negate(\"a\"); } finally { print \"cleanup\"; } }\n\
+             try { first(); } catch (e) { print e.kind; print e.trace; }\n\
+             try { throw 42; } catch (e) { print e; print e.value + 1; }\n\
+             fun answer() { try { return 1; } finally { print \"finally\"; } }\n\
+             print answer();",
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            printed,
+            "cleanup\nRuntimeError\nin negate, called from line 2\nin first, called from line 3\n\
+             Error: 42\n43\nfinally\n1\n"
+        );
+        // errors that were caught aren't reported
+        assert_eq!(diagnostics.take(), "");
+
+        let (printed, result) =
+            interpreter.eval_captured("try { throw \"boom\"; } finally { print \"still runs\"; }");
+        assert_eq!(printed, "still runs\n");
+        match result {
+            Err(ReefError::Thrown(error, report)) => {
+                assert_eq!(report, "[line 1] Error: boom");
+                assert_eq!(interpreter.stringify(&error), "Error: boom");
+            }
+            other => panic!("expected a thrown error, got {:?}", other),
+        }
+        assert_eq!(diagnostics.take(), "[line 1] Error: boom\n");
+
+        interpreter.set_step_budget(Some(1_000));
+        let result = interpreter.eval("try { while (true) {} } catch (e) { print \"caught\"; }");
+        assert!(matches!(
+            result,
+            Err(ReefError::LimitExceeded(Limit::Steps))
+        ));
+
+        // a return from finally can't outlive a budget either
+        let escape = "fun f() { try { while (true) {} } finally { return 1; } }\nprint f();";
+        let mut interpreter = Interpreter::new();
+        interpreter.set_step_budget(Some(1_000));
+        let (printed, result) = interpreter.eval_captured(escape);
+        assert_eq!(printed, "");
+        assert!(matches!(
+            result,
+            Err(ReefError::LimitExceeded(Limit::Steps))
+        ));
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interrupt_handle()
+            .store(true, Ordering::Relaxed);
+        let (printed, result) = interpreter.eval_captured(escape);
+        assert_eq!(printed, "");
+        assert!(matches!(
+            result,
+            Err(ReefError::LimitExceeded(Limit::Interrupted))
+        ));
+    }
+}
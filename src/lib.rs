@@ -1,14 +1,33 @@
 pub mod ast_printer;
+pub mod convert;
+pub mod dap;
 pub mod environment;
 pub mod error;
+pub mod exception;
 pub mod expr;
+pub mod formatter;
 pub mod func;
+pub mod gc;
+pub mod interner;
 pub mod interpreter;
+pub mod limits;
+pub mod linter;
+pub mod list;
+pub mod lsp;
+pub mod map;
+pub mod module;
+pub mod object;
+pub mod optimizer;
+pub mod output;
 pub mod parser;
 pub mod reef;
+pub mod resolver;
 pub mod scanner;
 pub mod stmt;
+pub mod suggest;
 pub mod token;
+pub mod visit;
+pub mod vm;
 pub use expr::{ExprKind, Value};
 pub use parser::Parser;
 pub use reef::Reef;
@@ -0,0 +1,233 @@
+use std::{
+    fmt,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use crate::error::ReefError;
+
+// steps between checks of the clock and the interrupt flag
+const CHECK_INTERVAL: u64 = 1024;
+
+// charges kept before dropped values are swept out, or twice the live
+// count if that's higher
+const SWEEP_THRESHOLD: usize = 1024;
+
+/// which budget a script ran out of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps,
+    Deadline,
+    Memory,
+    Interrupted,
+}
+
+impl Limit {
+    pub fn message(self) -> &'static str {
+        match self {
+            Limit::Steps => "step budget exhausted",
+            Limit::Deadline => "deadline exceeded",
+            Limit::Memory => "memory limit exceeded",
+            Limit::Interrupted => "interrupted",
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// bounds on how much a script may do, for running untrusted code. every
+/// statement and expression evaluated is a step, and environments,
+/// functions and new strings count against the memory cap by size for as
+/// long as they're alive
+#[derive(Debug, Default)]
+pub struct Limits {
+    steps: u64,
+    // step count at which the slow checks run next
+    next_check: u64,
+    // step count the budget runs out after
+    step_limit: Option<u64>,
+    deadline: Option<Instant>,
+    // only kept while there's a memory limit. some of these may have been
+    // dropped since the last sweep
+    charges: Vec<Charge>,
+    live: usize,
+    sweep_at: usize,
+    memory_limit: Option<usize>,
+    interrupt: Arc<AtomicBool>,
+    // set once the interrupt flag is seen, so every later step fails too
+    interrupted: bool,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Limits::default()
+    }
+
+    /// allows `budget` more steps, counted from now
+    pub fn set_step_budget(&mut self, budget: Option<u64>) {
+        self.step_limit = budget.map(|budget| self.steps.saturating_add(budget));
+        self.next_check = self.steps;
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+        self.next_check = self.steps;
+    }
+
+    pub fn set_memory_limit(&mut self, bytes: Option<usize>) {
+        self.memory_limit = bytes;
+        if bytes.is_none() {
+            self.charges.clear();
+            self.live = 0;
+        }
+    }
+
+    /// flag another thread can set to stop the script at its next check
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupt)
+    }
+
+    /// lets scripts run again after an interrupt stopped them
+    pub fn reset_interrupt(&mut self) {
+        self.interrupt.store(false, Ordering::Relaxed);
+        self.interrupted = false;
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn step(&mut self) -> Result<(), ReefError> {
+        self.steps += 1;
+        if self.steps < self.next_check {
+            return Ok(());
+        }
+        if self.step_limit.is_some_and(|limit| self.steps > limit) {
+            return Err(ReefError::limit_exceeded(Limit::Steps));
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(ReefError::limit_exceeded(Limit::Deadline));
+        }
+        if self.interrupted || self.interrupt.load(Ordering::Relaxed) {
+            self.interrupted = true;
+            return Err(ReefError::limit_exceeded(Limit::Interrupted));
+        }
+        self.next_check = self.steps + CHECK_INTERVAL;
+        if let Some(limit) = self.step_limit {
+            self.next_check = self.next_check.min(limit.saturating_add(1));
+        }
+        Ok(())
+    }
+
+    /// counts `bytes` against the memory cap until `owner` is dropped
+    pub fn charge<T: ?Sized + 'static>(
+        &mut self,
+        owner: &Rc<T>,
+        bytes: usize,
+    ) -> Result<(), ReefError> {
+        let Some(limit) = self.memory_limit else {
+            return Ok(());
+        };
+        let owner = Rc::downgrade(owner);
+        self.charges.push(Charge {
+            alive: Box::new(move || owner.strong_count() > 0),
+            bytes,
+        });
+        self.live += bytes;
+        if self.live > limit || self.charges.len() >= self.sweep_at {
+            self.sweep();
+        }
+        match self.live > limit {
+            true => Err(ReefError::limit_exceeded(Limit::Memory)),
+            false => Ok(()),
+        }
+    }
+
+    // forgets charges for values that have been dropped
+    fn sweep(&mut self) {
+        self.charges.retain(|charge| (charge.alive)());
+        self.live = self.charges.iter().map(|charge| charge.bytes).sum();
+        self.sweep_at = SWEEP_THRESHOLD.max(self.charges.len() * 2);
+    }
+}
+
+// bytes held by one charged value
+struct Charge {
+    alive: Box<dyn Fn() -> bool>,
+    bytes: usize,
+}
+
+impl fmt::Debug for Charge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Charge")
+            .field("bytes", &self.bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::atomic::Ordering;
+    use std::time::Instant;
+
+    use super::{Limit, Limits};
+    use crate::error::ReefError;
+
+    fn exceeded(result: Result<(), ReefError>) -> Option<Limit> {
+        match result {
+            Err(ReefError::LimitExceeded(limit)) => Some(limit),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_each_limit_is_distinct() {
+        let mut limits = Limits::new();
+        limits.set_step_budget(Some(3));
+        assert!((0..3).all(|_| limits.step().is_ok()));
+        assert_eq!(exceeded(limits.step()), Some(Limit::Steps));
+
+        let mut limits = Limits::new();
+        limits.set_deadline(Some(Instant::now()));
+        assert_eq!(exceeded(limits.step()), Some(Limit::Deadline));
+
+        // only what's still alive counts
+        let mut limits = Limits::new();
+        limits.set_memory_limit(Some(10));
+        let first: Rc<str> = "first".into();
+        assert!(limits.charge(&first, 10).is_ok());
+        assert_eq!(exceeded(limits.charge(&first, 1)), Some(Limit::Memory));
+        drop(first);
+        assert!(limits.charge(&Rc::<str>::from("second"), 10).is_ok());
+
+        // a budget counts from when it's set
+        let mut limits = Limits::new();
+        (0..100).for_each(|_| limits.step().unwrap());
+        limits.set_step_budget(Some(3));
+        assert!((0..3).all(|_| limits.step().is_ok()));
+        assert_eq!(exceeded(limits.step()), Some(Limit::Steps));
+
+        let mut limits = Limits::new();
+        limits.step().unwrap();
+        limits.interrupt_handle().store(true, Ordering::Relaxed);
+        let stopped = (0..2048).find_map(|_| exceeded(limits.step()));
+        assert_eq!(stopped, Some(Limit::Interrupted));
+        // stays stopped until the host resets it, even if the flag is cleared
+        limits.interrupt_handle().store(false, Ordering::Relaxed);
+        assert_eq!(exceeded(limits.step()), Some(Limit::Interrupted));
+        limits.reset_interrupt();
+        assert!(limits.step().is_ok());
+    }
+}
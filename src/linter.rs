@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::{
+    Literal, Token, TokenType,
+    expr::ExprKind,
+    interpreter::NATIVE_GLOBALS,
+    stmt::StmtKind,
+    visit::{DeclarationKind, Visitor, walk_expr, walk_stmt},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    Shadowing,
+    UnreachableCode,
+    NoEffect,
+    MixedLiteralComparison,
+    UndeclaredAssignment,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::UnusedVariable,
+        Lint::UnusedParameter,
+        Lint::Shadowing,
+        Lint::UnreachableCode,
+        Lint::NoEffect,
+        Lint::MixedLiteralComparison,
+        Lint::UndeclaredAssignment,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::Shadowing => "shadowing",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::NoEffect => "no-effect",
+            Lint::MixedLiteralComparison => "mixed-literal-comparison",
+            Lint::UndeclaredAssignment => "undeclared-assignment",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.id() == id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintLevel::Allow => write!(f, "allow"),
+            LintLevel::Warn => write!(f, "warning"),
+            LintLevel::Deny => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Lint, LintLevel>,
+}
+
+impl LintConfig {
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.levels.insert(lint, level);
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.levels.get(&lint).copied().unwrap_or(LintLevel::Warn)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub level: LintLevel,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] {}[{}]: {}",
+            self.line,
+            self.level,
+            self.lint.id(),
+            self.message
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Binding {
+    line: usize,
+    used: bool,
+    kind: DeclarationKind,
+}
+
+/// static checks over the parsed statement tree. locals are tracked
+/// scope by scope; globals are collected up front since functions may
+/// refer to globals declared further down the file
+pub struct Linter {
+    config: LintConfig,
+    globals: HashSet<String>,
+    scopes: Vec<HashMap<String, Binding>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    pub fn new(config: LintConfig) -> Self {
+        Linter {
+            config,
+            globals: HashSet::new(),
+            scopes: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn lint(mut self, stmts: &[StmtKind]) -> Vec<Diagnostic> {
+        self.globals = NATIVE_GLOBALS.iter().map(|name| name.to_string()).collect();
+        for stmt in stmts {
+            match stmt {
+                StmtKind::Var { name, .. } | StmtKind::Function { name, .. } => {
+                    self.globals.insert(name.lexeme.to_string());
+                }
+                StmtKind::Import { alias, names, .. } => {
+                    for name in alias.iter().chain(names) {
+                        self.globals.insert(name.lexeme.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.visit_statements(stmts);
+        self.diagnostics.sort_by_key(|d| d.line);
+        self.diagnostics
+    }
+
+    fn report(&mut self, lint: Lint, line: usize, message: String) {
+        let level = self.config.level(lint);
+        if level == LintLevel::Allow {
+            return;
+        }
+        self.diagnostics.push(Diagnostic {
+            lint,
+            level,
+            line,
+            message,
+        });
+    }
+
+    // true if `name` refers to something declared, marking it used
+    fn resolve(&mut self, name: &Token) -> bool {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.get_mut(&*name.lexeme) {
+                binding.used = true;
+                return true;
+            }
+        }
+        self.globals.contains(&*name.lexeme)
+    }
+}
+
+impl Visitor for Linter {
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let mut unused: Vec<(String, Binding)> = scope
+            .into_iter()
+            .filter(|(name, binding)| !binding.used && !name.starts_with('_'))
+            .collect();
+        unused.sort_by_key(|(_, binding)| binding.line);
+        for (name, binding) in unused {
+            match binding.kind {
+                DeclarationKind::Variable => self.report(
+                    Lint::UnusedVariable,
+                    binding.line,
+                    format!("variable '{}' is never used", name),
+                ),
+                DeclarationKind::Parameter => self.report(
+                    Lint::UnusedParameter,
+                    binding.line,
+                    format!("parameter '{}' is never used", name),
+                ),
+                // handlers often don't care what they caught
+                DeclarationKind::Function | DeclarationKind::Caught => {}
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Token, kind: DeclarationKind) {
+        if self.scopes.is_empty() {
+            return;
+        }
+        let depth = self.scopes.len() - 1;
+        let shadows_local = self.scopes[..depth]
+            .iter()
+            .any(|scope| scope.contains_key(&*name.lexeme));
+        if shadows_local || self.globals.contains(&*name.lexeme) {
+            self.report(
+                Lint::Shadowing,
+                name.line,
+                format!("'{}' shadows a variable from an outer scope", name.lexeme),
+            );
+        }
+        self.scopes[depth].insert(
+            name.lexeme.to_string(),
+            Binding {
+                line: name.line,
+                used: false,
+                kind,
+            },
+        );
+    }
+
+    fn reference(&mut self, name: &Token, assigned: bool) {
+        if !self.resolve(name) && assigned {
+            self.report(
+                Lint::UndeclaredAssignment,
+                name.line,
+                format!("assignment to undeclared variable '{}'", name.lexeme),
+            );
+        }
+    }
+
+    fn visit_statements(&mut self, stmts: &[StmtKind]) {
+        let mut exited = None;
+        for stmt in stmts {
+            if let Some(keyword) = exited.take()
+                && let Some(line) = stmt.line()
+            {
+                self.report(
+                    Lint::UnreachableCode,
+                    line,
+                    format!("unreachable statement after '{}'", keyword),
+                );
+            }
+            match stmt {
+                StmtKind::Return { .. } => exited = Some("return"),
+                StmtKind::Throw { .. } => exited = Some("throw"),
+                _ => {}
+            }
+            self.visit_stmt(stmt);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &StmtKind) {
+        if let StmtKind::Expression { expr } = stmt
+            && !has_effect(expr)
+        {
+            let line = expr.line().unwrap_or_default();
+            self.report(
+                Lint::NoEffect,
+                line,
+                "expression statement has no effect".to_string(),
+            );
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &ExprKind) {
+        if let ExprKind::Binary {
+            left,
+            operator,
+            right,
+        } = expr
+            && is_comparison(operator)
+            && let (ExprKind::Literal { value: l, .. }, ExprKind::Literal { value: r, .. }) =
+                (left.as_ref(), right.as_ref())
+            && literal_type(l) != literal_type(r)
+        {
+            self.report(
+                Lint::MixedLiteralComparison,
+                operator.line,
+                format!(
+                    "comparing {} with {} is always {}",
+                    literal_type(l),
+                    literal_type(r),
+                    operator.token_type == TokenType::BangEqual
+                ),
+            );
+        }
+        walk_expr(self, expr);
+    }
+}
+
+fn is_comparison(operator: &Token) -> bool {
+    matches!(
+        operator.token_type,
+        TokenType::EqualEqual | TokenType::BangEqual
+    )
+}
+
+fn literal_type(literal: &Literal) -> &'static str {
+    match literal {
+        Literal::String(_) => "string",
+        Literal::Number(_) => "number",
+        Literal::Boolean(_) => "boolean",
+        Literal::Nil => "nil",
+    }
+}
+
+fn has_effect(expr: &ExprKind) -> bool {
+    match expr {
+        ExprKind::Assign { .. } | ExprKind::Call { .. } | ExprKind::Set { .. } => true,
+        ExprKind::Binary { left, right, .. } | ExprKind::Logical { left, right, .. } => {
+            has_effect(left) || has_effect(right)
+        }
+        ExprKind::Get { object, .. } => has_effect(object),
+        ExprKind::Grouping { expression } => has_effect(expression),
+        ExprKind::Unary { right, .. } => has_effect(right),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lint, LintConfig, LintLevel, Linter};
+    use crate::{Parser, Scanner};
+
+    fn lint(source: &str, config: LintConfig) -> Vec<(Lint, usize)> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        Linter::new(config)
+            .lint(&stmts)
+            .into_iter()
+            .map(|d| (d.lint, d.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_hello_reef() {
+        let found = lint(include_str!("../reef/hello.reef"), LintConfig::default());
+        assert!(found.contains(&(Lint::Shadowing, 6)));
+        assert!(found.contains(&(Lint::Shadowing, 9)));
+        assert!(found.contains(&(Lint::NoEffect, 26)));
+        assert!(found.contains(&(Lint::NoEffect, 35)));
+        assert!(found.contains(&(Lint::MixedLiteralComparison, 32)));
+    }
+
+    #[test]
+    fn test_lint_scopes_and_returns() {
+        let source = "fun f(a, b) {\n  var unused = 1;\n  return a;\n  print b;\n}\nmissing = 2;\n";
+        let found = lint(source, LintConfig::default());
+        assert_eq!(
+            found,
+            vec![
+                (Lint::UnusedVariable, 2),
+                (Lint::UnreachableCode, 4),
+                (Lint::UndeclaredAssignment, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_levels() {
+        let mut config = LintConfig::default();
+        config.set(Lint::NoEffect, LintLevel::Allow);
+        assert!(lint("1 + 2;", config).is_empty());
+    }
+}
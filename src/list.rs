@@ -0,0 +1,68 @@
+use std::rc::Rc;
+
+use crate::{
+    expr::Value,
+    interpreter::{is_equal, stringify},
+    object::{HostObject, HostType},
+};
+
+/// what a rest parameter gathers: the arguments past the last named
+/// parameter, in order. scripts see it as a `List` object, and so do
+/// vectors the host hands over
+#[derive(Debug)]
+pub struct List {
+    pub items: Vec<Value>,
+}
+
+impl List {
+    /// the list inside a list object, if `value` is one
+    pub fn of(value: &Value) -> Option<&List> {
+        match value {
+            Value::Object(object) => object.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+fn list(object: &HostObject) -> &List {
+    object
+        .downcast_ref()
+        .expect("list objects always wrap a list")
+}
+
+thread_local! {
+    // lists only equal lists of the same type, so those made by the host
+    // share it with the interpreters'
+    static LIST_TYPE: Rc<HostType> = new_list_type();
+}
+
+/// the host type of lists, one per thread
+pub(crate) fn list_type() -> Rc<HostType> {
+    LIST_TYPE.with(Rc::clone)
+}
+
+fn new_list_type() -> Rc<HostType> {
+    let mut kind = HostType::new("List");
+    kind.property("length", |_, this| {
+        Ok(Value::Number(list(this).items.len() as f64))
+    })
+    .method("get", 1, |interpreter, this, arguments| {
+        let items = &list(this).items;
+        match arguments[0] {
+            Value::Number(index) if index.fract() == 0.0 && index >= 0.0 => items
+                .get(index as usize)
+                .cloned()
+                .ok_or_else(|| interpreter.host_error("list index out of range")),
+            _ => Err(interpreter.host_error("list index must be a whole number")),
+        }
+    })
+    .display(|this| {
+        let items: Vec<String> = list(this).items.iter().map(stringify).collect();
+        format!("[{}]", items.join(", "))
+    })
+    .equality(|a, b| {
+        let (a, b) = (&list(a).items, &list(b).items);
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| is_equal(a, b))
+    });
+    Rc::new(kind)
+}
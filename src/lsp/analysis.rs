@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use crate::{
+    Parser, Scanner, Token,
+    expr::ExprKind,
+    func::Arity,
+    stmt::StmtKind,
+    token::{Trivia, TriviaKind},
+    visit::{DeclarationKind, Visitor, walk_function},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Function,
+    Parameter,
+}
+
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub name: Token,
+    pub kind: SymbolKind,
+    pub parameters: Vec<String>,
+    // arguments a function takes, None for other symbols
+    pub arity: Option<Arity>,
+    pub doc: Option<String>,
+    pub parent: Option<usize>,
+}
+
+impl Declaration {
+    pub fn signature(&self) -> String {
+        match self.kind {
+            SymbolKind::Function => {
+                format!("fun {}({})", self.name.lexeme, self.parameters.join(", "))
+            }
+            SymbolKind::Variable => format!("var {}", self.name.lexeme),
+            SymbolKind::Parameter => format!("(parameter) {}", self.name.lexeme),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub message: String,
+}
+
+/// everything the language server knows about one document: declarations,
+/// every identifier resolved back to its declaration, and diagnostics
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub declarations: Vec<Declaration>,
+    pub references: Vec<(Token, usize)>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+        let mut analysis = Analysis::default();
+        for (line, column, error) in scanner.errors() {
+            analysis.diagnostics.push(Diagnostic {
+                line: *line,
+                column: *column,
+                length: 1,
+                message: error.message().to_string(),
+            });
+        }
+        let (stmts, errors) = Parser::new(tokens).parse_recovering();
+        for (token, error) in errors {
+            analysis.diagnostics.push(Diagnostic {
+                line: token.line,
+                column: token.column,
+                length: token.lexeme.len().max(1),
+                message: error.message().to_string(),
+            });
+        }
+
+        let mut resolver = Resolver {
+            analysis: &mut analysis,
+            docs: doc_comments(scanner.trivia()),
+            globals: HashMap::new(),
+            scopes: Vec::new(),
+            parent: None,
+        };
+        resolver.resolve_program(&stmts);
+        analysis
+    }
+
+    /// declaration named or referenced by the token under the cursor
+    pub fn declaration_at(&self, line: usize, column: usize) -> Option<usize> {
+        let covers = |token: &Token| {
+            token.line == line
+                && token.column <= column
+                && column <= token.column + token.lexeme.len()
+        };
+        self.declarations
+            .iter()
+            .position(|decl| covers(&decl.name))
+            .or_else(|| {
+                self.references
+                    .iter()
+                    .find(|(token, _)| covers(token))
+                    .map(|(_, decl)| *decl)
+            })
+    }
+
+    pub fn references_to(&self, declaration: usize) -> impl Iterator<Item = &Token> {
+        self.references
+            .iter()
+            .filter(move |(_, decl)| *decl == declaration)
+            .map(|(token, _)| token)
+    }
+
+    pub fn hover(&self, declaration: usize) -> String {
+        let decl = &self.declarations[declaration];
+        let mut text = format!("```reef\n{}\n```", decl.signature());
+        if let Some(arity) = decl.arity {
+            text.push_str(&format!("\narity: {}", arity));
+        }
+        if let Some(doc) = &decl.doc {
+            text.push_str("\n\n");
+            text.push_str(doc);
+        }
+        text
+    }
+
+    pub fn children(&self, parent: Option<usize>) -> impl Iterator<Item = (usize, &Declaration)> {
+        self.declarations
+            .iter()
+            .enumerate()
+            .filter(move |(_, decl)| decl.parent == parent && decl.kind != SymbolKind::Parameter)
+    }
+}
+
+// own-line comment runs keyed by the line just below them
+fn doc_comments(trivia: &[Trivia]) -> HashMap<usize, String> {
+    let mut comments: HashMap<usize, &str> = HashMap::new();
+    for t in trivia {
+        if let TriviaKind::Comment(text) = &t.kind
+            && !t.trailing
+        {
+            comments.insert(t.line, text);
+        }
+    }
+    let mut docs = HashMap::new();
+    for &line in comments.keys() {
+        if comments.contains_key(&(line + 1)) {
+            continue;
+        }
+        let mut start = line;
+        while start > 1 && comments.contains_key(&(start - 1)) {
+            start -= 1;
+        }
+        let doc: Vec<&str> = (start..=line)
+            .map(|l| comments[&l].trim_start_matches('/').trim())
+            .collect();
+        docs.insert(line + 1, doc.join("\n"));
+    }
+    docs
+}
+
+struct Resolver<'a> {
+    analysis: &'a mut Analysis,
+    docs: HashMap<usize, String>,
+    globals: HashMap<String, usize>,
+    scopes: Vec<HashMap<String, usize>>,
+    parent: Option<usize>,
+}
+
+// arguments a function declared with these parameters accepts
+fn function_arity(parameters: &[Token], defaults: &[ExprKind], rest: Option<&Token>) -> Arity {
+    Arity::of_parameters(parameters.len(), defaults.len(), rest.is_some())
+}
+
+fn parameter_names(parameters: &[Token], rest: Option<&Token>) -> Vec<String> {
+    let mut names: Vec<String> = parameters.iter().map(|p| p.lexeme.to_string()).collect();
+    names.extend(rest.map(|rest| format!("...{}", rest.lexeme)));
+    names
+}
+
+impl Resolver<'_> {
+    fn resolve_program(&mut self, stmts: &[StmtKind]) {
+        // globals are visible everywhere, including function bodies that
+        // appear above the declaration
+        for stmt in stmts {
+            match stmt {
+                StmtKind::Var { name, .. } => {
+                    let id = self.add_declaration(name, SymbolKind::Variable, Vec::new(), None);
+                    self.globals.entry(name.lexeme.to_string()).or_insert(id);
+                }
+                StmtKind::Function {
+                    name,
+                    parameters,
+                    defaults,
+                    rest,
+                    ..
+                } => {
+                    let params = parameter_names(parameters, rest.as_ref());
+                    let arity = function_arity(parameters, defaults, rest.as_ref());
+                    let id = self.add_declaration(name, SymbolKind::Function, params, Some(arity));
+                    self.globals.entry(name.lexeme.to_string()).or_insert(id);
+                }
+                StmtKind::Import { alias, names, .. } => {
+                    for name in alias.iter().chain(names) {
+                        let id = self.add_declaration(name, SymbolKind::Variable, Vec::new(), None);
+                        self.globals.entry(name.lexeme.to_string()).or_insert(id);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.visit_statements(stmts);
+    }
+
+    fn add_declaration(
+        &mut self,
+        name: &Token,
+        kind: SymbolKind,
+        parameters: Vec<String>,
+        arity: Option<Arity>,
+    ) -> usize {
+        self.analysis.declarations.push(Declaration {
+            name: name.clone(),
+            kind,
+            parameters,
+            arity,
+            doc: self.docs.get(&name.line).cloned(),
+            parent: self.parent,
+        });
+        self.analysis.declarations.len() - 1
+    }
+
+    fn declare_symbol(
+        &mut self,
+        name: &Token,
+        kind: SymbolKind,
+        parameters: Vec<String>,
+        arity: Option<Arity>,
+    ) -> usize {
+        if self.scopes.is_empty() {
+            // top level declarations were registered up front
+            let found =
+                self.analysis.declarations.iter().position(|decl| {
+                    decl.name.line == name.line && decl.name.column == name.column
+                });
+            if let Some(id) = found {
+                return id;
+            }
+        }
+        let id = self.add_declaration(name, kind, parameters, arity);
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(name.lexeme.to_string(), id);
+            }
+            None => {
+                self.globals.entry(name.lexeme.to_string()).or_insert(id);
+            }
+        }
+        id
+    }
+}
+
+impl Visitor for Resolver<'_> {
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token, kind: DeclarationKind) {
+        let kind = match kind {
+            DeclarationKind::Parameter => SymbolKind::Parameter,
+            DeclarationKind::Function => SymbolKind::Function,
+            DeclarationKind::Variable | DeclarationKind::Caught => SymbolKind::Variable,
+        };
+        self.declare_symbol(name, kind, Vec::new(), None);
+    }
+
+    fn reference(&mut self, name: &Token, _assigned: bool) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&*name.lexeme))
+            .or_else(|| self.globals.get(&*name.lexeme));
+        if let Some(&id) = found {
+            self.analysis.references.push((name.clone(), id));
+        }
+    }
+
+    // symbols declared in a function's body are its children
+    fn visit_function_declaration(
+        &mut self,
+        name: &Token,
+        parameters: &[Token],
+        defaults: &[ExprKind],
+        rest: Option<&Token>,
+        body: &[StmtKind],
+    ) {
+        let params = parameter_names(parameters, rest);
+        let arity = function_arity(parameters, defaults, rest);
+        let id = self.declare_symbol(name, SymbolKind::Function, params, Some(arity));
+        let enclosing = self.parent.replace(id);
+        walk_function(self, parameters, defaults, rest, body);
+        self.parent = enclosing;
+    }
+}
@@ -0,0 +1,288 @@
+pub mod analysis;
+pub mod transport;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{Value, json};
+
+use crate::{Token, interpreter::NATIVE_GLOBALS, scanner::KEYWORDS};
+use analysis::{Analysis, Declaration, SymbolKind};
+use transport::{read_body, write_message};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+
+// lsp enum values
+const SYMBOL_FUNCTION: u8 = 12;
+const SYMBOL_VARIABLE: u8 = 13;
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_KEYWORD: u8 = 14;
+const SEVERITY_ERROR: u8 = 1;
+
+/// language server speaking json-rpc over any reader/writer pair, so
+/// `reef lsp` can use stdio and tests can drive it with in-memory buffers
+pub struct Server<R, W> {
+    reader: R,
+    writer: W,
+    documents: HashMap<String, Analysis>,
+    shutdown: bool,
+}
+
+pub fn run_stdio() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    Server::new(stdin.lock(), stdout.lock()).run()
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Server {
+            reader,
+            writer,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        while let Some(body) = read_body(&mut self.reader)? {
+            let message: Value = match serde_json::from_slice(&body) {
+                Ok(message) => message,
+                Err(e) => {
+                    // there's no id to answer to, but the client should
+                    // still hear about it
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": { "code": PARSE_ERROR, "message": e.to_string() },
+                    });
+                    write_message(&mut self.writer, &response)?;
+                    continue;
+                }
+            };
+            let method = message["method"].as_str().unwrap_or_default().to_string();
+            if method == "exit" {
+                break;
+            }
+            let params = &message["params"];
+            match message.get("id") {
+                Some(id) => {
+                    let response = match self.handle_request(&method, params) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err((code, error)) => json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": code, "message": error },
+                        }),
+                    };
+                    write_message(&mut self.writer, &response)?;
+                }
+                None => self.handle_notification(&method, params)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        if self.shutdown {
+            return Err((INVALID_REQUEST, "server is shutting down".to_string()));
+        }
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "documentSymbolProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "reef" },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/documentSymbol" => {
+                let (_, analysis) = self.document(params)?;
+                Ok(Value::Array(document_symbols(analysis, None)))
+            }
+            "textDocument/definition" => {
+                let (uri, analysis) = self.document(params)?;
+                Ok(match declaration_at(analysis, params) {
+                    Some(decl) => location(uri, &analysis.declarations[decl].name),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/references" => {
+                let (uri, analysis) = self.document(params)?;
+                let Some(decl) = declaration_at(analysis, params) else {
+                    return Ok(json!([]));
+                };
+                let mut locations = Vec::new();
+                if params["context"]["includeDeclaration"].as_bool() == Some(true) {
+                    locations.push(location(uri, &analysis.declarations[decl].name));
+                }
+                locations.extend(
+                    analysis
+                        .references_to(decl)
+                        .map(|token| location(uri, token)),
+                );
+                Ok(Value::Array(locations))
+            }
+            "textDocument/hover" => {
+                let (_, analysis) = self.document(params)?;
+                Ok(match declaration_at(analysis, params) {
+                    Some(decl) => json!({
+                        "contents": { "kind": "markdown", "value": analysis.hover(decl) },
+                    }),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/completion" => {
+                let (_, analysis) = self.document(params)?;
+                Ok(Value::Array(completions(analysis)))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method: {}", method))),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), Analysis::new(text));
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didChange" => {
+                // full sync, the last change holds the whole document
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), Analysis::new(text));
+                }
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics: Vec<Value> = self
+            .documents
+            .get(uri)
+            .map(|analysis| {
+                analysis
+                    .diagnostics
+                    .iter()
+                    .map(|d| {
+                        json!({
+                            "range": range(d.line, d.column, d.length),
+                            "severity": SEVERITY_ERROR,
+                            "source": "reef",
+                            "message": d.message,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        });
+        write_message(&mut self.writer, &notification)
+    }
+
+    fn document<'a>(&'a self, params: &'a Value) -> Result<(&'a str, &'a Analysis), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents
+            .get(uri)
+            .map(|analysis| (uri, analysis))
+            .ok_or((INVALID_REQUEST, format!("unknown document: {}", uri)))
+    }
+}
+
+fn declaration_at(analysis: &Analysis, params: &Value) -> Option<usize> {
+    let line = params["position"]["line"].as_u64()? as usize;
+    let column = params["position"]["character"].as_u64()? as usize;
+    analysis.declaration_at(line + 1, column)
+}
+
+// reef lines are 1-based, lsp lines 0-based
+fn range(line: usize, column: usize, length: usize) -> Value {
+    let line = line.saturating_sub(1);
+    json!({
+        "start": { "line": line, "character": column },
+        "end": { "line": line, "character": column + length },
+    })
+}
+
+fn location(uri: &str, token: &Token) -> Value {
+    json!({ "uri": uri, "range": range(token.line, token.column, token.lexeme.len()) })
+}
+
+fn document_symbols(analysis: &Analysis, parent: Option<usize>) -> Vec<Value> {
+    analysis
+        .children(parent)
+        .map(|(id, decl)| {
+            let token_range = range(decl.name.line, decl.name.column, decl.name.lexeme.len());
+            json!({
+                "name": &*decl.name.lexeme,
+                "detail": decl.signature(),
+                "kind": symbol_kind(decl),
+                "range": token_range,
+                "selectionRange": token_range,
+                "children": document_symbols(analysis, Some(id)),
+            })
+        })
+        .collect()
+}
+
+fn symbol_kind(decl: &Declaration) -> u8 {
+    match decl.kind {
+        SymbolKind::Function => SYMBOL_FUNCTION,
+        SymbolKind::Variable | SymbolKind::Parameter => SYMBOL_VARIABLE,
+    }
+}
+
+fn completions(analysis: &Analysis) -> Vec<Value> {
+    let mut items: Vec<Value> = KEYWORDS
+        .iter()
+        .map(|(keyword, _)| json!({ "label": keyword, "kind": COMPLETION_KEYWORD }))
+        .collect();
+    items.extend(NATIVE_GLOBALS.iter().map(
+        |name| json!({ "label": name, "kind": COMPLETION_FUNCTION, "detail": "native function" }),
+    ));
+    let mut seen = Vec::new();
+    for decl in &analysis.declarations {
+        if seen.contains(&&decl.name.lexeme) {
+            continue;
+        }
+        seen.push(&decl.name.lexeme);
+        let kind = match decl.kind {
+            SymbolKind::Function => COMPLETION_FUNCTION,
+            _ => COMPLETION_VARIABLE,
+        };
+        items
+            .push(json!({ "label": &*decl.name.lexeme, "kind": kind, "detail": decl.signature() }));
+    }
+    items
+}
@@ -1,9 +1,24 @@
 #![allow(unused_variables, dead_code)]
-use std::env;
+use std::{env, thread};
 
-use reef_interpreter::reef::Reef;
+use reef_interpreter::linter::{Lint, LintConfig, LintLevel};
+use reef_interpreter::reef::{Backend, Reef};
+
+// reef calls recurse on the rust stack, so give the interpreter room for
+// DEFAULT_MAX_CALL_DEPTH of them and then some
+const STACK_SIZE: usize = 1 << 30;
 
 fn main() {
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("failed to start the interpreter thread");
+    if interpreter.join().is_err() {
+        std::process::exit(70)
+    }
+}
+
+fn run() {
     let args: Vec<String> = env::args().collect();
 
     let command = &args[1];
@@ -14,6 +29,110 @@ fn main() {
             let filename = &args[2];
             reef.run_file(filename);
         }
+        "run" => {
+            let filename = &args[2];
+            let mut dump_ast = false;
+            let mut flags = args[3..].iter();
+            while let Some(flag) = flags.next() {
+                match flag.as_str() {
+                    "-O" | "--optimize" => reef.set_optimize(true),
+                    "--dump-ast" | "--dump-ast=parsed" => dump_ast = true,
+                    "--dump-ast=optimized" => {
+                        dump_ast = true;
+                        reef.set_optimize(true);
+                    }
+                    "--backend" => {
+                        let name = flags.next().map(String::as_str).unwrap_or_default();
+                        let Some(backend) = Backend::from_name(name) else {
+                            eprintln!("Unknown backend: {}", name);
+                            std::process::exit(64)
+                        };
+                        reef.set_backend(backend);
+                    }
+                    "--max-call-depth" => {
+                        let depth = flags.next().and_then(|depth| depth.parse().ok());
+                        let Some(depth) = depth else {
+                            eprintln!("--max-call-depth expects a number");
+                            std::process::exit(64)
+                        };
+                        reef.set_max_call_depth(depth);
+                    }
+                    "--gc-stats" => reef.set_gc_stats(true),
+                    "--gc-stress" => reef.set_gc_stress(true),
+                    _ => {
+                        eprintln!("Unknown run flag: {}", flag);
+                        std::process::exit(64)
+                    }
+                }
+            }
+            if dump_ast {
+                reef.dump_ast(filename);
+            } else {
+                reef.run_script(filename);
+            }
+        }
+        "compile" => {
+            let filename = &args[2];
+            let mut output = None;
+            let mut flags = args[3..].iter();
+            while let Some(flag) = flags.next() {
+                match flag.as_str() {
+                    "-o" | "--output" => output = flags.next().map(String::as_str),
+                    _ => {
+                        eprintln!("Unknown compile flag: {}", flag);
+                        std::process::exit(64)
+                    }
+                }
+            }
+            reef.compile_file(filename, output);
+        }
+        "disasm" => {
+            let filename = &args[2];
+            reef.disassemble_file(filename);
+        }
+        "fmt" => {
+            let filename = &args[2];
+            let check = args[3..].iter().any(|arg| arg == "--check");
+            reef.format_file(filename, check);
+        }
+        "lint" => {
+            let filename = &args[2];
+            let mut config = LintConfig::default();
+            let mut flags = args[3..].iter();
+            while let Some(flag) = flags.next() {
+                let level = match flag.as_str() {
+                    "-A" | "--allow" => LintLevel::Allow,
+                    "-W" | "--warn" => LintLevel::Warn,
+                    "-D" | "--deny" => LintLevel::Deny,
+                    _ => {
+                        eprintln!("Unknown lint flag: {}", flag);
+                        std::process::exit(64)
+                    }
+                };
+                let id = flags.next().map(String::as_str).unwrap_or_default();
+                if id == "all" {
+                    Lint::ALL.iter().for_each(|lint| config.set(*lint, level));
+                } else if let Some(lint) = Lint::from_id(id) {
+                    config.set(lint, level);
+                } else {
+                    eprintln!("Unknown lint: {}", id);
+                    std::process::exit(64)
+                }
+            }
+            reef.lint_file(filename, config);
+        }
+        "lsp" => {
+            if let Err(e) = reef_interpreter::lsp::run_stdio() {
+                eprintln!("language server failed: {}", e);
+                std::process::exit(74)
+            }
+        }
+        "dap" => {
+            if let Err(e) = reef_interpreter::dap::run_stdio() {
+                eprintln!("debug adapter failed: {}", e);
+                std::process::exit(74)
+            }
+        }
         "repl" => {
             let _ = reef.run_repl();
         }
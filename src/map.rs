@@ -0,0 +1,68 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    expr::Value,
+    interpreter::{is_equal, stringify},
+    object::{HostObject, HostType},
+};
+
+/// string-keyed table the host hands a script, seen as a `Map` object
+#[derive(Debug)]
+pub struct Map {
+    pub entries: HashMap<Rc<str>, Value>,
+}
+
+impl Map {
+    /// the map inside a map object, if `value` is one
+    pub fn of(value: &Value) -> Option<&Map> {
+        match value {
+            Value::Object(object) => object.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+fn map(object: &HostObject) -> &Map {
+    object
+        .downcast_ref()
+        .expect("map objects always wrap a map")
+}
+
+thread_local! {
+    static MAP_TYPE: Rc<HostType> = new_map_type();
+}
+
+/// the host type of maps, one per thread
+pub(crate) fn map_type() -> Rc<HostType> {
+    MAP_TYPE.with(Rc::clone)
+}
+
+fn new_map_type() -> Rc<HostType> {
+    let mut kind = HostType::new("Map");
+    kind.property("length", |_, this| {
+        Ok(Value::Number(map(this).entries.len() as f64))
+    })
+    .method("get", 1, |interpreter, this, arguments| {
+        match &arguments[0] {
+            Value::String(key) => Ok(map(this).entries.get(key).cloned().unwrap_or(Value::Nil)),
+            _ => Err(interpreter.host_error("map keys must be strings")),
+        }
+    })
+    .display(|this| {
+        // sorted, so printing doesn't depend on hash order
+        let mut entries: Vec<String> = map(this)
+            .entries
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key, stringify(value)))
+            .collect();
+        entries.sort();
+        format!("{{{}}}", entries.join(", "))
+    })
+    .equality(|a, b| {
+        let (a, b) = (&map(a).entries, &map(b).entries);
+        a.len() == b.len()
+            && a.iter()
+                .all(|(key, value)| b.get(key).is_some_and(|other| is_equal(value, other)))
+    });
+    Rc::new(kind)
+}
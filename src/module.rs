@@ -0,0 +1,327 @@
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{environment::EnvRef, expr::Value, suggest::did_you_mean};
+
+/// where imported modules come from. a loader turns an import path into
+/// the name of a module, then that name into source. modules with the
+/// same name are the same module, run once per interpreter
+pub trait ModuleLoader {
+    /// name of the module `path` refers to when imported from the module
+    /// `importer`, or from the host when there's none
+    fn resolve(&self, path: &str, importer: Option<&str>) -> Result<String, String>;
+    /// source of a module `resolve` named
+    fn load(&self, name: &str) -> Result<String, String>;
+}
+
+/// a module that has run, and the top level scope it ran in
+pub struct Module {
+    pub name: String,
+    scope: EnvRef,
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Module")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Module {
+    pub(crate) fn new(name: String, scope: EnvRef) -> Self {
+        Module { name, scope }
+    }
+
+    pub(crate) fn scope(&self) -> &EnvRef {
+        &self.scope
+    }
+
+    /// a name the module defined at its top level
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.scope.borrow().lookup(name)
+    }
+
+    /// everything the module defined at its top level, sorted by name
+    pub fn exports(&self) -> Vec<(String, Value)> {
+        self.scope.borrow().bindings()
+    }
+
+    /// like `get`, but says what went wrong
+    pub(crate) fn export(&self, name: &str) -> Result<Value, String> {
+        if let Some(value) = self.get(name) {
+            return Ok(value);
+        }
+        let mut message = format!("module '{}' has no export '{}'", self.name, name);
+        let exports = self.exports();
+        let names = exports.iter().map(|(name, _)| name.as_str());
+        if let Some(suggestion) = did_you_mean(name, names) {
+            message.push_str(&format!(", did you mean '{}'?", suggestion));
+        }
+        Err(message)
+    }
+}
+
+// joins `path` onto `base` without touching the filesystem, folding
+// away `.` and `..`
+fn join(base: &Path, path: &str) -> PathBuf {
+    let mut joined = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::ParentDir => {
+                joined.pop();
+            }
+            Component::CurDir => {}
+            component => joined.push(component),
+        }
+    }
+    joined
+}
+
+fn directory_of(importer: Option<&str>) -> PathBuf {
+    importer
+        .and_then(|importer| Path::new(importer).parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// loads modules from disk, relative to the importing file (or the
+/// working directory for the host) and then each search path directory
+#[derive(Debug, Clone, Default)]
+pub struct FileLoader {
+    search_path: Vec<PathBuf>,
+}
+
+impl FileLoader {
+    pub fn new(search_path: Vec<PathBuf>) -> Self {
+        FileLoader { search_path }
+    }
+
+    /// searches the directories listed in `REEF_PATH`
+    pub fn from_env() -> Self {
+        let search_path = env::var_os("REEF_PATH")
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        FileLoader::new(search_path)
+    }
+}
+
+impl ModuleLoader for FileLoader {
+    fn resolve(&self, path: &str, importer: Option<&str>) -> Result<String, String> {
+        let relative = directory_of(importer).join(path);
+        let candidates =
+            std::iter::once(relative).chain(self.search_path.iter().map(|dir| dir.join(path)));
+        for candidate in candidates {
+            if let Ok(found) = candidate.canonicalize() {
+                return Ok(found.to_string_lossy().into_owned());
+            }
+        }
+        Err(format!("module '{}' not found", path))
+    }
+
+    fn load(&self, name: &str) -> Result<String, String> {
+        fs::read_to_string(name).map_err(|e| format!("can't read module '{}': {}", name, e))
+    }
+}
+
+/// loads modules from disk, but only from inside `root`. meant for
+/// untrusted scripts, which shouldn't be able to read arbitrary files
+#[derive(Debug, Clone)]
+pub struct SandboxLoader {
+    root: PathBuf,
+}
+
+impl SandboxLoader {
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, String> {
+        let root = root
+            .as_ref()
+            .canonicalize()
+            .map_err(|e| format!("bad sandbox root: {}", e))?;
+        Ok(SandboxLoader { root })
+    }
+}
+
+impl ModuleLoader for SandboxLoader {
+    fn resolve(&self, path: &str, importer: Option<&str>) -> Result<String, String> {
+        let base = match importer {
+            Some(_) => directory_of(importer),
+            None => self.root.clone(),
+        };
+        let outside = || format!("module '{}' is outside the sandbox", path);
+        let joined = join(&base, path);
+        if !joined.starts_with(&self.root) {
+            return Err(outside());
+        }
+        // a symlink inside the root may still point out of it
+        let found = joined
+            .canonicalize()
+            .map_err(|_| format!("module '{}' not found", path))?;
+        if !found.starts_with(&self.root) {
+            return Err(outside());
+        }
+        Ok(found.to_string_lossy().into_owned())
+    }
+
+    fn load(&self, name: &str) -> Result<String, String> {
+        fs::read_to_string(name).map_err(|e| format!("can't read module '{}': {}", name, e))
+    }
+}
+
+/// modules kept in memory by path, for tests and hosts that bundle their
+/// scripts. paths resolve relative to the importer like files would
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLoader {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        MemoryLoader::default()
+    }
+
+    pub fn insert(&mut self, path: &str, source: &str) -> &mut Self {
+        let name = join(Path::new(""), path).to_string_lossy().into_owned();
+        self.modules.insert(name, source.to_string());
+        self
+    }
+}
+
+impl ModuleLoader for MemoryLoader {
+    fn resolve(&self, path: &str, importer: Option<&str>) -> Result<String, String> {
+        let name = join(&directory_of(importer), path)
+            .to_string_lossy()
+            .into_owned();
+        if !self.modules.contains_key(&name) {
+            return Err(format!("module '{}' not found", path));
+        }
+        Ok(name)
+    }
+
+    fn load(&self, name: &str) -> Result<String, String> {
+        self.modules
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("module '{}' not found", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, rc::Rc};
+
+    use super::{MemoryLoader, ModuleLoader, SandboxLoader};
+    use crate::{
+        Parser, Scanner, error::ReefError, expr::Value, interpreter::Interpreter, output::Capture,
+    };
+
+    fn runtime_error(result: Result<impl std::fmt::Debug, ReefError>) -> String {
+        match result {
+            Err(ReefError::RuntimeError(message)) => message,
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_modules_run_once_in_their_own_scope() {
+        let mut loader = MemoryLoader::new();
+        loader.insert(
+            "lib/counter.reef",
+            "print \"loading\";\n\
+             var count = 0;\n\
+             fun bump() { count = count + 1; return count; }",
+        );
+        let mut interpreter = Interpreter::new();
+        interpreter.set_module_loader(Box::new(loader));
+
+        let output = Capture::new();
+        interpreter.set_output(Box::new(output.clone()));
+        let counter = interpreter.import("lib/counter.reef").unwrap();
+        let again = interpreter.import("./lib/../lib/counter.reef").unwrap();
+        assert!(Rc::ptr_eq(&counter, &again));
+        assert_eq!(output.take(), "loading\n");
+
+        // the module's globals stay with it, even when called from outside
+        assert!(interpreter.get_global("count").is_none());
+        interpreter.define_global("count", Value::Number(100.0));
+        let bump = counter.get("bump").unwrap();
+        interpreter.call(&bump, Vec::new()).unwrap();
+        let Value::Number(count) = interpreter.call(&bump, Vec::new()).unwrap() else {
+            panic!("expected a number");
+        };
+        assert_eq!(count, 2.0);
+        let names: Vec<String> = counter
+            .exports()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["bump", "count"]);
+
+        assert!(
+            runtime_error(interpreter.import("lib/missing.reef"))
+                .ends_with("module 'lib/missing.reef' not found")
+        );
+    }
+
+    #[test]
+    fn test_modules_only_see_builtins() {
+        let mut loader = MemoryLoader::new();
+        loader
+            .insert("peek.reef", "var seen = secret;")
+            .insert("host.reef", "var late = shared + 1; var now = clock() > 0;");
+        let mut interpreter = Interpreter::new();
+        interpreter.set_module_loader(Box::new(loader));
+        let tokens = Scanner::new("var secret = 1;".to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        interpreter.interpret(stmts).unwrap();
+
+        assert!(
+            runtime_error(interpreter.import("peek.reef")).contains("undefined variable: 'secret'")
+        );
+        interpreter.define_global("shared", Value::Number(1.0));
+        let host = interpreter.import("host.reef").unwrap();
+        assert!(matches!(host.get("late"), Some(Value::Number(2.0))));
+    }
+
+    #[test]
+    fn test_import_cycles_are_errors() {
+        let mut loader = MemoryLoader::new();
+        loader
+            .insert("a.reef", "import_b();")
+            .insert("b.reef", "import_a();");
+        let mut interpreter = Interpreter::new();
+        interpreter.set_module_loader(Box::new(loader));
+        for (name, path) in [("import_a", "a.reef"), ("import_b", "b.reef")] {
+            interpreter.register_fn(name, 0, move |interpreter, _| {
+                interpreter.import(path).map(|_| Value::Nil)
+            });
+        }
+        assert!(
+            runtime_error(interpreter.import("a.reef"))
+                .ends_with("import cycle: a.reef -> b.reef -> a.reef")
+        );
+    }
+
+    #[test]
+    fn test_sandbox_stays_inside_its_root() {
+        let root = env::temp_dir().join(format!("reef_sandbox_{}", std::process::id()));
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::write(root.join("lib/util.reef"), "var x = 1;").unwrap();
+        let loader = SandboxLoader::new(&root).unwrap();
+
+        let util = loader.resolve("lib/util.reef", None).unwrap();
+        assert!(loader.load(&util).is_ok());
+        assert_eq!(
+            loader.resolve("../lib/./util.reef", Some(&util)),
+            Ok(util.clone())
+        );
+        assert_eq!(
+            loader.resolve("../../etc/passwd", Some(&util)),
+            Err("module '../../etc/passwd' is outside the sandbox".to_string())
+        );
+        assert!(loader.resolve("/etc/passwd", None).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,348 @@
+use std::{any::Any, collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    error::ReefError,
+    expr::Value,
+    func::{Arity, ReefCallable},
+    interpreter::Interpreter,
+    suggest::did_you_mean,
+};
+
+/// a method of a host type, handed the object it was called on
+pub type MethodFn = dyn Fn(&mut Interpreter, &HostObject, Vec<Value>) -> Result<Value, ReefError>;
+pub type GetterFn = dyn Fn(&mut Interpreter, &HostObject) -> Result<Value, ReefError>;
+pub type SetterFn = dyn Fn(&mut Interpreter, &HostObject, Value) -> Result<(), ReefError>;
+type DisplayFn = dyn Fn(&HostObject) -> String;
+type EqualsFn = dyn Fn(&HostObject, &HostObject) -> bool;
+
+struct Method {
+    // `Type.method`, what scripts see when they print it
+    name: Rc<str>,
+    arity: Arity,
+    func: Box<MethodFn>,
+}
+
+struct Property {
+    get: Box<GetterFn>,
+    set: Option<Box<SetterFn>>,
+}
+
+/// what scripts can do with one kind of host object. objects of a type
+/// print as `<Name object>` and are only equal to themselves unless the
+/// host says otherwise
+pub struct HostType {
+    pub name: Rc<str>,
+    methods: HashMap<Rc<str>, Rc<Method>>,
+    properties: HashMap<Rc<str>, Property>,
+    display: Option<Box<DisplayFn>>,
+    equals: Option<Box<EqualsFn>>,
+}
+
+impl fmt::Debug for HostType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostType")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HostType {
+    pub fn new(name: &str) -> Self {
+        HostType {
+            name: name.into(),
+            methods: HashMap::new(),
+            properties: HashMap::new(),
+            display: None,
+            equals: None,
+        }
+    }
+
+    /// `object.name(...)` calls `func` with the object
+    pub fn method<F>(&mut self, name: &str, arity: impl Into<Arity>, func: F) -> &mut Self
+    where
+        F: Fn(&mut Interpreter, &HostObject, Vec<Value>) -> Result<Value, ReefError> + 'static,
+    {
+        let method = Method {
+            name: format!("{}.{}", self.name, name).into(),
+            arity: arity.into(),
+            func: Box::new(func),
+        };
+        self.methods.insert(name.into(), Rc::new(method));
+        self
+    }
+
+    /// read-only `object.name`
+    pub fn property<G>(&mut self, name: &str, get: G) -> &mut Self
+    where
+        G: Fn(&mut Interpreter, &HostObject) -> Result<Value, ReefError> + 'static,
+    {
+        let property = Property {
+            get: Box::new(get),
+            set: None,
+        };
+        self.properties.insert(name.into(), property);
+        self
+    }
+
+    /// `object.name` that scripts can also assign to
+    pub fn property_mut<G, S>(&mut self, name: &str, get: G, set: S) -> &mut Self
+    where
+        G: Fn(&mut Interpreter, &HostObject) -> Result<Value, ReefError> + 'static,
+        S: Fn(&mut Interpreter, &HostObject, Value) -> Result<(), ReefError> + 'static,
+    {
+        let property = Property {
+            get: Box::new(get),
+            set: Some(Box::new(set)),
+        };
+        self.properties.insert(name.into(), property);
+        self
+    }
+
+    /// how `print` and `stringify` show objects of this type
+    pub fn display(&mut self, display: impl Fn(&HostObject) -> String + 'static) -> &mut Self {
+        self.display = Some(Box::new(display));
+        self
+    }
+
+    /// compares two objects of this type for `==`
+    pub fn equality(
+        &mut self,
+        equals: impl Fn(&HostObject, &HostObject) -> bool + 'static,
+    ) -> &mut Self {
+        self.equals = Some(Box::new(equals));
+        self
+    }
+
+    /// wraps `data` as a reef value of this type
+    pub fn instance<T: Any>(self: &Rc<Self>, data: T) -> Value {
+        Value::Object(Rc::new(HostObject {
+            kind: Rc::clone(self),
+            data: Box::new(data),
+        }))
+    }
+
+    fn unknown_property(&self, name: &str) -> String {
+        let mut message = format!("{} has no property '{}'", self.name, name);
+        let names = self.properties.keys().chain(self.methods.keys());
+        if let Some(suggestion) = did_you_mean(name, names.map(|name| &**name)) {
+            message.push_str(&format!(", did you mean '{}'?", suggestion));
+        }
+        message
+    }
+}
+
+/// a rust value handed to scripts as is. scripts only reach it through
+/// its type's methods and properties
+pub struct HostObject {
+    kind: Rc<HostType>,
+    data: Box<dyn Any>,
+}
+
+impl fmt::Debug for HostObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostObject")
+            .field("kind", &self.kind.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HostObject {
+    pub fn kind(&self) -> &Rc<HostType> {
+        &self.kind
+    }
+
+    /// the wrapped rust value, if it is a `T`
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+
+    pub(crate) fn display(&self) -> String {
+        match &self.kind.display {
+            Some(display) => display(self),
+            None => format!("<{} object>", self.kind.name),
+        }
+    }
+
+    pub(crate) fn equals(self: &Rc<Self>, other: &Rc<HostObject>) -> bool {
+        if Rc::ptr_eq(self, other) {
+            return true;
+        }
+        match &self.kind.equals {
+            Some(equals) if Rc::ptr_eq(&self.kind, &other.kind) => equals(self, other),
+            _ => false,
+        }
+    }
+
+    /// reads a property, or a method bound to this object. the outer error
+    /// says what's missing, the caller adds where
+    pub(crate) fn get(
+        self: &Rc<Self>,
+        interpreter: &mut Interpreter,
+        name: &str,
+    ) -> Result<Result<Value, ReefError>, String> {
+        if let Some(property) = self.kind.properties.get(name) {
+            return Ok((property.get)(interpreter, self));
+        }
+        match self.kind.methods.get(name) {
+            Some(method) => Ok(Ok(Value::Callable(Rc::new(BoundMethod {
+                receiver: Rc::clone(self),
+                method: Rc::clone(method),
+            })))),
+            None => Err(self.kind.unknown_property(name)),
+        }
+    }
+
+    pub(crate) fn set(
+        self: &Rc<Self>,
+        interpreter: &mut Interpreter,
+        name: &str,
+        value: Value,
+    ) -> Result<Result<(), ReefError>, String> {
+        match self.kind.properties.get(name) {
+            Some(Property { set: Some(set), .. }) => Ok(set(interpreter, self, value)),
+            Some(_) => Err(format!("{}.{} is read-only", self.kind.name, name)),
+            None => Err(self.kind.unknown_property(name)),
+        }
+    }
+}
+
+// `object.method` without the call, remembering its object
+struct BoundMethod {
+    receiver: Rc<HostObject>,
+    method: Rc<Method>,
+}
+
+impl fmt::Debug for BoundMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoundMethod")
+            .field("name", &self.method.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReefCallable for BoundMethod {
+    fn arity(&self) -> usize {
+        match self.method.arity {
+            Arity::Exactly(arity) | Arity::AtLeast(arity) | Arity::Between(arity, _) => arity,
+        }
+    }
+    fn arity_spec(&self) -> Arity {
+        self.method.arity
+    }
+    fn name(&self) -> &str {
+        &self.method.name
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, ReefError> {
+        (self.method.func)(interpreter, &self.receiver, arguments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use super::HostType;
+    use crate::{error::ReefError, expr::Value, interpreter::Interpreter};
+
+    struct Counter {
+        label: RefCell<String>,
+        count: Cell<f64>,
+    }
+
+    fn counter_type() -> Rc<HostType> {
+        let mut counter = HostType::new("Counter");
+        counter
+            .method("add", 1, |interpreter, this, arguments| {
+                let counter = this.downcast_ref::<Counter>().unwrap();
+                let Value::Number(n) = arguments[0] else {
+                    return Err(interpreter.host_error("add: expected a number"));
+                };
+                counter.count.set(counter.count.get() + n);
+                Ok(Value::Nil)
+            })
+            .property("count", |_, this| {
+                let counter = this.downcast_ref::<Counter>().unwrap();
+                Ok(Value::Number(counter.count.get()))
+            })
+            .property_mut(
+                "label",
+                |_, this| {
+                    let counter = this.downcast_ref::<Counter>().unwrap();
+                    Ok(Value::String(counter.label.borrow().as_str().into()))
+                },
+                |_, this, value| {
+                    let counter = this.downcast_ref::<Counter>().unwrap();
+                    *counter.label.borrow_mut() = value.as_string()?.to_string();
+                    Ok(())
+                },
+            )
+            .display(|this| {
+                let counter = this.downcast_ref::<Counter>().unwrap();
+                format!("<counter {}>", counter.label.borrow())
+            })
+            .equality(|a, b| {
+                let a = a.downcast_ref::<Counter>().unwrap();
+                let b = b.downcast_ref::<Counter>().unwrap();
+                *a.label.borrow() == *b.label.borrow()
+            });
+        Rc::new(counter)
+    }
+
+    fn counter(kind: &Rc<HostType>, label: &str) -> Value {
+        kind.instance(Counter {
+            label: RefCell::new(label.to_string()),
+            count: Cell::new(0.0),
+        })
+    }
+
+    #[test]
+    fn test_host_objects() {
+        let kind = counter_type();
+        let mut interpreter = Interpreter::new();
+        interpreter.define_global("a", counter(&kind, "a"));
+        interpreter.define_global("b", counter(&kind, "b"));
+
+        let (printed, result) = interpreter.eval_captured(
+            "a.add(2);\n\
+             var add = a.add;\n\
+             add(3);\n\
+             print a.count;\n\
+             print a;\n\
+             print add;\n\
+             print a == b;\n\
+             b.label = \"a\";\n\
+             print a == b;\n\
+             print a == a;",
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            printed,
+            "5\n<counter a>\n<fn Counter.add>\nfalse\ntrue\ntrue\n"
+        );
+
+        let error = |interpreter: &mut Interpreter, source: &str| match interpreter.eval(source) {
+            Err(ReefError::RuntimeError(message)) => message,
+            other => panic!("expected a runtime error, got {:?}", other),
+        };
+        assert!(
+            error(&mut interpreter, "a.cuont;")
+                .ends_with("Counter has no property 'cuont', did you mean 'count'?")
+        );
+        assert!(error(&mut interpreter, "a.count = 1;").ends_with("Counter.count is read-only"));
+        assert!(
+            error(&mut interpreter, "(1).count;")
+                .ends_with("can't read property 'count' of a number")
+        );
+        assert!(error(&mut interpreter, "a.add(\"x\");").ends_with("add: expected a number"));
+        // add indexes its arguments, so a wrong count must stop before it runs
+        assert!(error(&mut interpreter, "a.add();").ends_with("Expected: 1 args, got 0 args"));
+        assert!(error(&mut interpreter, "a.add(1, 2);").ends_with("Expected: 1 args, got 2 args"));
+        let (printed, _) = interpreter.eval_captured("print a.count;");
+        assert_eq!(printed, "5\n");
+    }
+}
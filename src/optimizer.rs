@@ -0,0 +1,317 @@
+use crate::{Literal, Token, TokenType, expr::ExprKind, stmt::StmtKind};
+
+/// optional pass over the parsed tree: folds operators whose operands are
+/// all literals and drops branches and loops a literal condition rules
+/// out. anything that could fail at runtime (`"a" - 1`, `-nil`) is left
+/// alone so the error still happens, on the same line
+pub struct Optimizer;
+
+impl Optimizer {
+    pub fn optimize(stmts: Vec<StmtKind>) -> Vec<StmtKind> {
+        stmts.into_iter().filter_map(Self::optimize_stmt).collect()
+    }
+
+    // None when the statement can never run
+    fn optimize_stmt(stmt: StmtKind) -> Option<StmtKind> {
+        Some(match stmt {
+            StmtKind::Print { expr } => StmtKind::Print {
+                expr: Self::fold(expr),
+            },
+            StmtKind::Expression { expr } => StmtKind::Expression {
+                expr: Self::fold(expr),
+            },
+            StmtKind::Var { name, initializer } => StmtKind::Var {
+                name,
+                initializer: Self::fold(initializer),
+            },
+            StmtKind::Block { statements } => StmtKind::Block {
+                statements: Self::optimize(statements),
+            },
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = Self::fold(condition);
+                match Self::truthiness(&condition) {
+                    Some(true) => return Self::optimize_stmt(*then_branch),
+                    Some(false) => return else_branch.and_then(|e| Self::optimize_stmt(*e)),
+                    None => StmtKind::If {
+                        condition,
+                        then_branch: Box::new(Self::optimize_branch(*then_branch)),
+                        else_branch: else_branch
+                            .and_then(|e| Self::optimize_stmt(*e))
+                            .map(Box::new),
+                    },
+                }
+            }
+            StmtKind::While { condition, body } => {
+                let condition = Self::fold(condition);
+                if Self::truthiness(&condition) == Some(false) {
+                    return None;
+                }
+                StmtKind::While {
+                    condition,
+                    body: Box::new(Self::optimize_branch(*body)),
+                }
+            }
+            StmtKind::Function {
+                name,
+                parameters,
+                defaults,
+                rest,
+                body,
+            } => StmtKind::Function {
+                name,
+                parameters,
+                defaults: defaults.into_iter().map(Self::fold).collect(),
+                rest,
+                body: Self::optimize(body),
+            },
+            StmtKind::Return { keyword, value } => StmtKind::Return {
+                keyword,
+                value: Self::fold(value),
+            },
+            StmtKind::Throw { keyword, value } => StmtKind::Throw {
+                keyword,
+                value: Self::fold(value),
+            },
+            StmtKind::Try {
+                keyword,
+                body,
+                catch,
+                finally,
+            } => StmtKind::Try {
+                keyword,
+                body: Self::optimize(body),
+                catch: catch.map(|(name, handler)| (name, Self::optimize(handler))),
+                finally: finally.map(Self::optimize),
+            },
+            stmt @ (StmtKind::Import { .. } | StmtKind::Error { .. }) => stmt,
+        })
+    }
+
+    // a statement that has to stay, even if there's nothing left of it
+    fn optimize_branch(stmt: StmtKind) -> StmtKind {
+        Self::optimize_stmt(stmt).unwrap_or(StmtKind::Block {
+            statements: Vec::new(),
+        })
+    }
+
+    fn truthiness(expr: &ExprKind) -> Option<bool> {
+        match expr {
+            ExprKind::Literal { value, .. } => {
+                Some(!matches!(value, Literal::Nil | Literal::Boolean(false)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn fold(expr: ExprKind) -> ExprKind {
+        match expr {
+            ExprKind::Grouping { expression } => match Self::fold(*expression) {
+                literal @ ExprKind::Literal { .. } => literal,
+                expression => ExprKind::Grouping {
+                    expression: Box::new(expression),
+                },
+            },
+            ExprKind::Unary { operator, right } => {
+                let right = Self::fold(*right);
+                let value = match (&right, operator.token_type) {
+                    (ExprKind::Literal { .. }, TokenType::Bang) => {
+                        Self::truthiness(&right).map(|truthy| Literal::Boolean(!truthy))
+                    }
+                    (
+                        ExprKind::Literal {
+                            value: Literal::Number(n),
+                            ..
+                        },
+                        TokenType::Minus,
+                    ) => Some(Literal::Number(-n)),
+                    _ => None,
+                };
+                match value {
+                    Some(value) => ExprKind::Literal {
+                        value,
+                        line: operator.line,
+                    },
+                    None => ExprKind::Unary {
+                        operator,
+                        right: Box::new(right),
+                    },
+                }
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = Self::fold(*left);
+                let right = Self::fold(*right);
+                match (&left, &right) {
+                    (ExprKind::Literal { value: l, line }, ExprKind::Literal { value: r, .. }) => {
+                        match Self::binary(l, &operator, r) {
+                            Some(value) => ExprKind::Literal { value, line: *line },
+                            None => ExprKind::Binary {
+                                left: Box::new(left),
+                                operator,
+                                right: Box::new(right),
+                            },
+                        }
+                    }
+                    _ => ExprKind::Binary {
+                        left: Box::new(left),
+                        operator,
+                        right: Box::new(right),
+                    },
+                }
+            }
+            ExprKind::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let left = Self::fold(*left);
+                let right = Self::fold(*right);
+                // `and` keeps a falsy left operand, `or` a truthy one
+                match Self::truthiness(&left) {
+                    Some(truthy) if truthy == (operator.token_type == TokenType::Or) => left,
+                    Some(_) => right,
+                    None => ExprKind::Logical {
+                        left: Box::new(left),
+                        operator,
+                        right: Box::new(right),
+                    },
+                }
+            }
+            ExprKind::Assign {
+                name,
+                value,
+                binding,
+            } => ExprKind::Assign {
+                name,
+                value: Box::new(Self::fold(*value)),
+                binding,
+            },
+            ExprKind::Call {
+                callee,
+                token,
+                arguments,
+                named,
+            } => ExprKind::Call {
+                callee: Box::new(Self::fold(*callee)),
+                token,
+                arguments: arguments.into_iter().map(Self::fold).collect(),
+                named: named
+                    .into_iter()
+                    .map(|(name, arg)| (name, Self::fold(arg)))
+                    .collect(),
+            },
+            ExprKind::Function {
+                name,
+                parameters,
+                defaults,
+                rest,
+                body,
+            } => ExprKind::Function {
+                name,
+                parameters,
+                defaults: defaults.into_iter().map(Self::fold).collect(),
+                rest,
+                body: Self::optimize(body),
+            },
+            ExprKind::Get { object, name } => ExprKind::Get {
+                object: Box::new(Self::fold(*object)),
+                name,
+            },
+            ExprKind::Set {
+                object,
+                name,
+                value,
+            } => ExprKind::Set {
+                object: Box::new(Self::fold(*object)),
+                name,
+                value: Box::new(Self::fold(*value)),
+            },
+            expr => expr,
+        }
+    }
+
+    // mirrors the interpreter's binary operators, None wherever it would
+    // raise an error
+    fn binary(left: &Literal, operator: &Token, right: &Literal) -> Option<Literal> {
+        use Literal::{Boolean, Number, String};
+        Some(match (operator.token_type, left, right) {
+            (TokenType::Plus, Number(l), Number(r)) => Number(l + r),
+            (TokenType::Plus, String(l), String(r)) => String(format!("{}{}", l, r).into()),
+            (TokenType::Minus, Number(l), Number(r)) => Number(l - r),
+            (TokenType::Star, Number(l), Number(r)) => Number(l * r),
+            (TokenType::Slash, Number(l), Number(r)) => Number(l / r),
+            (TokenType::Greater, Number(l), Number(r)) => Boolean(l > r),
+            (TokenType::GreaterEqual, Number(l), Number(r)) => Boolean(l >= r),
+            (TokenType::Less, Number(l), Number(r)) => Boolean(l < r),
+            (TokenType::LessEqual, Number(l), Number(r)) => Boolean(l <= r),
+            (TokenType::EqualEqual, l, r) => Boolean(Self::equal(l, r)),
+            (TokenType::BangEqual, l, r) => Boolean(!Self::equal(l, r)),
+            _ => return None,
+        })
+    }
+
+    fn equal(left: &Literal, right: &Literal) -> bool {
+        match (left, right) {
+            (Literal::Number(l), Literal::Number(r)) => l == r,
+            (Literal::String(l), Literal::String(r)) => l == r,
+            (Literal::Boolean(l), Literal::Boolean(r)) => l == r,
+            (Literal::Nil, Literal::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Optimizer;
+    use crate::ast_printer::AstPrinter;
+    use crate::{Parser, Scanner};
+
+    fn optimized(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        Optimizer::optimize(stmts)
+            .iter()
+            .map(AstPrinter::print_stmt)
+            .collect()
+    }
+
+    #[test]
+    fn test_folds_constants() {
+        assert_eq!(
+            optimized("2 * 4; !!!!!!!!!!!nil == false; \"my \" + \"words\"; (1 + 2) < 4;"),
+            vec!["(expr 8)", "(expr false)", "(expr my words)", "(expr true)"]
+        );
+        assert_eq!(optimized("print false or x;"), vec!["(print x)"]);
+        assert_eq!(optimized("print nil and x;"), vec!["(print nil)"]);
+    }
+
+    #[test]
+    fn test_keeps_runtime_errors() {
+        assert_eq!(
+            optimized("\"a\" - 1; -nil; 1 < \"b\";"),
+            vec!["(expr (- a 1))", "(expr (- nil))", "(expr (< 1 b))"]
+        );
+    }
+
+    #[test]
+    fn test_removes_dead_branches() {
+        assert_eq!(
+            optimized(
+                "if (false) print 1; if (1 > 2) print 2; else print 3; while (false) print 4;"
+            ),
+            vec!["(print 3)"]
+        );
+        assert_eq!(
+            optimized("while (x) if (nil) print 1;"),
+            vec!["(while x (block))"]
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+thread_local! {
+    // where error reports go while an interpreter with its own
+    // diagnostics sink is running on this thread
+    static DIAGNOSTICS: RefCell<Option<Box<dyn Write>>> = const { RefCell::new(None) };
+}
+
+/// reports an error to the diagnostics sink in effect, stderr by default
+pub fn diagnostic(message: &str) {
+    let written = DIAGNOSTICS.with(|sink| match sink.borrow_mut().as_mut() {
+        Some(sink) => {
+            let _ = writeln!(sink, "{}", message);
+            true
+        }
+        None => false,
+    });
+    if !written {
+        eprintln!("{}", message);
+    }
+}
+
+/// runs `run` with error reports going to `sink` (or stderr when None),
+/// handing the sink back afterwards
+pub fn with_diagnostics<T>(
+    sink: Option<Box<dyn Write>>,
+    run: impl FnOnce() -> T,
+) -> (T, Option<Box<dyn Write>>) {
+    if sink.is_none() {
+        return (run(), None);
+    }
+    let previous = DIAGNOSTICS.with(|current| current.replace(sink));
+    let result = run();
+    let sink = DIAGNOSTICS.with(|current| current.replace(previous));
+    (result, sink)
+}
+
+/// runs `run` holding back the error reports it makes, for the caller to
+/// pass on with `diagnostic` or drop
+pub fn hold_diagnostics<T>(run: impl FnOnce() -> T) -> (T, String) {
+    let held = Capture::new();
+    let (result, _) = with_diagnostics(Some(Box::new(held.clone())), run);
+    (result, held.take())
+}
+
+/// in-memory sink, clones share the same buffer
+#[derive(Debug, Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    pub fn new() -> Self {
+        Capture::default()
+    }
+
+    /// everything written so far, emptying the buffer
+    pub fn take(&self) -> String {
+        String::from_utf8_lossy(&self.0.take()).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Capture, diagnostic, with_diagnostics};
+
+    #[test]
+    fn test_diagnostics_go_to_the_installed_sink() {
+        let capture = Capture::new();
+        let ((), sink) = with_diagnostics(Some(Box::new(capture.clone())), || {
+            diagnostic("first");
+            diagnostic("second");
+        });
+        assert!(sink.is_some());
+        assert_eq!(capture.take(), "first\nsecond\n");
+        // uninstalled again
+        diagnostic("to stderr");
+        assert_eq!(capture.take(), "");
+    }
+}
@@ -1,13 +1,30 @@
 #![allow(unused_variables, dead_code)]
 
+use std::rc::Rc;
+
 use crate::{
     Literal, Reef, Token, TokenType,
     environment::Environment,
     error::ReefError,
-    expr::ExprKind,
+    expr::{Binding, ExprKind},
     stmt::{Stmt, StmtKind},
 };
 
+// stands in for the name anonymous functions don't have
+fn anonymous(keyword: &Token) -> Token {
+    Token {
+        lexeme: "anonymous".into(),
+        ..keyword.clone()
+    }
+}
+
+// what's between the parens of a function
+struct Parameters {
+    names: Vec<Token>,
+    defaults: Vec<ExprKind>,
+    rest: Option<Token>,
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
@@ -62,6 +79,12 @@ impl Parser {
         false
     }
 
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| &token.token_type == token_type)
+    }
+
     fn check(&self, token_type: &TokenType) -> bool {
         if self.is_at_end() {
             false
@@ -73,7 +96,7 @@ impl Parser {
 
     pub fn parse(&mut self) -> Result<Vec<StmtKind>, ReefError> {
         while !self.is_at_eof() {
-            match self.declaration() {
+            match self.top_level_declaration() {
                 Ok(stmt) => self.statements.push(stmt),
                 Err(e) => return Err(e),
             }
@@ -81,24 +104,125 @@ impl Parser {
         Ok(self.statements.clone())
     }
 
-    fn declaration(&mut self) -> Result<StmtKind, ReefError> {
-        let decl_result = {
-            if self.match_type(&[TokenType::Fun]) {
-                return self.function("function");
+    /// parses as much of the program as possible, collecting every error
+    /// with the token it was reported at rather than stopping at the first
+    pub fn parse_recovering(&mut self) -> (Vec<StmtKind>, Vec<(Token, ReefError)>) {
+        let mut errors = Vec::new();
+        while !self.is_at_eof() {
+            match self.top_level_declaration() {
+                Ok(stmt) => self.statements.push(stmt),
+                Err(e) => {
+                    let token = self
+                        .peek()
+                        .or(self.tokens.last())
+                        .expect("should always have an eof token")
+                        .clone();
+                    errors.push((token, e));
+                    self.synchronize();
+                }
             }
-            if self.match_type(&[TokenType::Var]) {
-                return self.var_declaration();
+        }
+        (self.statements.clone(), errors)
+    }
+
+    /// parses a lone expression, e.g. one typed into a debugger
+    pub fn parse_expression(&mut self) -> Result<ExprKind, ReefError> {
+        let expr = self.expression()?;
+        if !self.is_at_eof() {
+            return Err(ReefError::reef_error_at_line(
+                self.peek().expect("should be a token here"),
+                "expected end of expression",
+            ));
+        }
+        Ok(expr)
+    }
+
+    // `from` is only a keyword at the start of an import, so scripts can
+    // keep using it as a name
+    fn at_from_import(&self) -> bool {
+        let from = self.peek().is_some_and(|token| {
+            token.token_type == TokenType::Identifier && &*token.lexeme == "from"
+        });
+        let path = self
+            .tokens
+            .get(self.current + 1)
+            .is_some_and(|token| token.token_type == TokenType::String);
+        from && path
+    }
+
+    // imports are only allowed here, so a module's imports are known
+    // before any of it runs
+    fn top_level_declaration(&mut self) -> Result<StmtKind, ReefError> {
+        if self.check(&TokenType::Import) || self.at_from_import() {
+            return self.import_declaration();
+        }
+        self.declaration()
+    }
+
+    fn import_declaration(&mut self) -> Result<StmtKind, ReefError> {
+        let keyword = self.advance().expect("should be an import token").clone();
+        let from = keyword.token_type == TokenType::Identifier;
+        let path = match &self
+            .consume(TokenType::String, "expect module path after import")?
+            .literal
+        {
+            Some(Literal::String(path)) => Rc::clone(path),
+            _ => unreachable!("string tokens carry their text"),
+        };
+        let mut alias = None;
+        let mut names = Vec::new();
+        if from {
+            self.consume(TokenType::Import, "expect 'import' after module path")?;
+            loop {
+                names.push(
+                    self.consume(TokenType::Identifier, "expect name to import")?
+                        .clone(),
+                );
+                if !self.match_type(&[TokenType::Comma]) {
+                    break;
+                }
             }
-            if self.match_type(&[TokenType::Fun]) {
-                // todo: logic for this stmt type
+        } else {
+            let is_as = self.peek().is_some_and(|token| {
+                token.token_type == TokenType::Identifier && &*token.lexeme == "as"
+            });
+            if !is_as {
+                return Err(ReefError::reef_error_at_line(
+                    self.peek().expect("should be a token here"),
+                    "expect 'as' after module path",
+                ));
             }
-            self.statement()
-        };
-        match &decl_result {
-            Ok(_) => {}
-            Err(e) => self.synchronize(),
+            self.advance();
+            alias = Some(
+                self.consume(TokenType::Identifier, "expect module name after 'as'")?
+                    .clone(),
+            );
+        }
+        self.consume(TokenType::Semicolon, "expected ';' after import")?;
+        Ok(StmtKind::Import {
+            keyword,
+            path,
+            alias,
+            names,
+        })
+    }
+
+    fn declaration(&mut self) -> Result<StmtKind, ReefError> {
+        if self.check(&TokenType::Import) || self.at_from_import() {
+            return Err(ReefError::reef_error_at_line(
+                self.peek().expect("should be a token here"),
+                "imports must be at the top level",
+            ));
+        }
+        // `fun (` starts a function expression instead
+        if self.check(&TokenType::Fun) && !self.check_next(&TokenType::LeftParen) {
+            self.advance();
+            return self.function("function");
         }
-        decl_result
+        if self.match_type(&[TokenType::Var]) {
+            return self.var_declaration();
+        }
+        self.statement()
     }
 
     fn var_declaration(&mut self) -> Result<StmtKind, ReefError> {
@@ -120,35 +244,136 @@ impl Parser {
             )?
             .clone();
         self.consume(TokenType::LeftParen, "expect '(' before function params")?;
-        let mut parameters: Vec<Token> = Vec::new();
+        let parameters = self.parameters()?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("expect '{{' before {} body", { kind }),
+        )?;
+
+        let body = self.block_statements()?;
+        Ok(StmtKind::Function {
+            name: name.clone(),
+            parameters: parameters.names,
+            defaults: parameters.defaults,
+            rest: parameters.rest,
+            body,
+        })
+    }
+
+    // after the '(', up to and including the ')'. parameters with a
+    // default come after those without, a rest parameter comes last
+    fn parameters(&mut self) -> Result<Parameters, ReefError> {
+        let mut parameters = Parameters {
+            names: Vec::new(),
+            defaults: Vec::new(),
+            rest: None,
+        };
         if !&self.check(&TokenType::RightParen) {
             loop {
-                if parameters.len() >= 255 {
+                if parameters.names.len() >= 255 {
                     return Err(ReefError::reef_error_at_line(
                         self.peek().unwrap(),
                         "can't have more than 255 params",
                     ));
                 }
+                if self.match_type(&[TokenType::Ellipsis]) {
+                    let rest = self
+                        .consume(
+                            TokenType::Identifier,
+                            "expect rest parameter name after '...'",
+                        )?
+                        .clone();
+                    parameters.rest = Some(rest);
+                    if self.check(&TokenType::Comma) {
+                        return Err(ReefError::reef_error_at_line(
+                            self.peek().expect("should be a token here"),
+                            "rest parameter must be last",
+                        ));
+                    }
+                    break;
+                }
                 let identifier = self
                     .consume(TokenType::Identifier, "expect parameter name")?
                     .clone();
-                parameters.push(identifier.clone());
+                if self.match_type(&[TokenType::Equal]) {
+                    parameters.defaults.push(self.expression()?);
+                } else if !parameters.defaults.is_empty() {
+                    return Err(ReefError::reef_error_at_line(
+                        &identifier,
+                        "parameter without a default can't follow one with a default",
+                    ));
+                }
+                parameters.names.push(identifier);
                 if !self.match_type(&[TokenType::Comma]) {
                     break;
                 }
             }
         }
         self.consume(TokenType::RightParen, "Expect ')' after params")?;
+        Ok(parameters)
+    }
 
-        self.consume(
-            TokenType::LeftBrace,
-            &format!("expect '{{' before {} body", { kind }),
-        )?;
-
+    // `fun (a, b) { ... }`, the `fun` already consumed
+    fn function_expression(&mut self) -> Result<ExprKind, ReefError> {
+        let keyword = self.previous().expect("should be a fun token").clone();
+        self.consume(TokenType::LeftParen, "expect '(' after fun")?;
+        let parameters = self.parameters()?;
+        self.consume(TokenType::LeftBrace, "expect '{' before function body")?;
         let body = self.block_statements()?;
-        Ok(StmtKind::Function {
-            name: name.clone(),
-            parameters,
+        Ok(ExprKind::Function {
+            name: anonymous(&keyword),
+            parameters: parameters.names,
+            defaults: parameters.defaults,
+            rest: parameters.rest,
+            body,
+        })
+    }
+
+    // whether a `(` starts an arrow function's parameter list rather than
+    // a grouping, which takes looking past the matching `)`
+    fn at_arrow_function(&self) -> bool {
+        if !self.check(&TokenType::LeftParen) {
+            return false;
+        }
+        let mut tokens = self.tokens[self.current..].iter();
+        let mut depth = 0;
+        for token in tokens.by_ref() {
+            match token.token_type {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+        }
+        tokens
+            .next()
+            .is_some_and(|token| token.token_type == TokenType::Arrow)
+    }
+
+    // `(a, b) => a + b`, or with a block body `(a) => { ... }`
+    fn arrow_function(&mut self) -> Result<ExprKind, ReefError> {
+        self.advance();
+        let parameters = self.parameters()?;
+        let arrow = self
+            .consume(TokenType::Arrow, "expect '=>' after parameters")?
+            .clone();
+        let body = if self.match_type(&[TokenType::LeftBrace]) {
+            self.block_statements()?
+        } else {
+            let value = self.expression()?;
+            vec![StmtKind::Return {
+                keyword: arrow.clone(),
+                value,
+            }]
+        };
+        Ok(ExprKind::Function {
+            name: anonymous(&arrow),
+            parameters: parameters.names,
+            defaults: parameters.defaults,
+            rest: parameters.rest,
             body,
         })
     }
@@ -160,8 +385,12 @@ impl Parser {
                 TokenType::For => self.for_statement(),
                 TokenType::If => self.if_statement(),
                 TokenType::Print => self.print_statement(),
+                TokenType::Return => self.return_statement(),
+                TokenType::Throw => self.throw_statement(),
+                TokenType::Try => self.try_statement(),
                 TokenType::While => self.while_statement(),
                 TokenType::LeftBrace => {
+                    self.advance();
                     let statements = self.block_statements()?;
                     Ok(StmtKind::Block { statements })
                 }
@@ -171,7 +400,7 @@ impl Parser {
         }
     }
     fn for_statement(&mut self) -> Result<StmtKind, ReefError> {
-        self.advance();
+        let line = self.advance().expect("should be a for token").line;
         self.consume(TokenType::LeftParen, "expect '(' to begin for loop")?;
         let mut initializer = None;
         if self.match_type(&[TokenType::Semicolon]) {
@@ -204,6 +433,7 @@ impl Parser {
         if condition.is_none() {
             condition = Some(ExprKind::Literal {
                 value: Literal::Boolean(true),
+                line,
             })
         }
         body = StmtKind::While {
@@ -258,7 +488,6 @@ impl Parser {
     }
 
     fn block_statements(&mut self) -> Result<Vec<StmtKind>, ReefError> {
-        self.advance();
         let mut statements: Vec<StmtKind> = Vec::new();
         while !self.match_type(&[TokenType::RightBrace]) && !self.is_at_end() {
             let decl = self.declaration()?;
@@ -282,6 +511,56 @@ impl Parser {
         Ok(StmtKind::Print { expr })
     }
 
+    fn return_statement(&mut self) -> Result<StmtKind, ReefError> {
+        let keyword = self.advance().expect("should be a return token").clone();
+        let mut value = ExprKind::None;
+        if !self.check(&TokenType::Semicolon) {
+            value = self.expression()?;
+        }
+        self.consume(TokenType::Semicolon, "expected ';' after return value")?;
+        Ok(StmtKind::Return { keyword, value })
+    }
+
+    fn throw_statement(&mut self) -> Result<StmtKind, ReefError> {
+        let keyword = self.advance().expect("should be a throw token").clone();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "expected ';' after thrown value")?;
+        Ok(StmtKind::Throw { keyword, value })
+    }
+
+    fn try_statement(&mut self) -> Result<StmtKind, ReefError> {
+        let keyword = self.advance().expect("should be a try token").clone();
+        self.consume(TokenType::LeftBrace, "expect '{' after try")?;
+        let body = self.block_statements()?;
+        let mut catch = None;
+        if self.match_type(&[TokenType::Catch]) {
+            self.consume(TokenType::LeftParen, "expect '(' after catch")?;
+            let name = self
+                .consume(TokenType::Identifier, "expect name for the caught error")?
+                .clone();
+            self.consume(TokenType::RightParen, "expect ')' after caught name")?;
+            self.consume(TokenType::LeftBrace, "expect '{' before catch body")?;
+            catch = Some((name, self.block_statements()?));
+        }
+        let mut finally = None;
+        if self.match_type(&[TokenType::Finally]) {
+            self.consume(TokenType::LeftBrace, "expect '{' after finally")?;
+            finally = Some(self.block_statements()?);
+        }
+        if catch.is_none() && finally.is_none() {
+            return Err(ReefError::reef_error_at_line(
+                self.peek().expect("should be a token here"),
+                "expect 'catch' or 'finally' after try block",
+            ));
+        }
+        Ok(StmtKind::Try {
+            keyword,
+            body,
+            catch,
+            finally,
+        })
+    }
+
     fn or_expression(&mut self) -> Result<ExprKind, ReefError> {
         let mut expr = self.and_expression()?;
         while self.match_type(&[TokenType::Or]) {
@@ -330,10 +609,18 @@ impl Parser {
             let value = self.assignment()?;
 
             match expr {
-                ExprKind::Variable { name } => {
+                ExprKind::Variable { name, .. } => {
                     return Ok(ExprKind::Assign {
                         name,
                         value: Box::new(value),
+                        binding: Binding::Unresolved,
+                    });
+                }
+                ExprKind::Get { object, name } => {
+                    return Ok(ExprKind::Set {
+                        object,
+                        name,
+                        value: Box::new(value),
                     });
                 }
                 _ => {
@@ -419,16 +706,33 @@ impl Parser {
 
     fn finish_call(&mut self, callee: ExprKind) -> Result<ExprKind, ReefError> {
         let mut arguments: Vec<ExprKind> = Vec::new();
+        let mut named: Vec<(Token, ExprKind)> = Vec::new();
         if !self.check(&TokenType::RightParen) {
             loop {
-                if arguments.len() >= 255 {
+                if arguments.len() + named.len() >= 255 {
                     return Err(ReefError::reef_error_at_line(
                         self.peek().expect("should be a preceding token"),
                         "can't have more than 255 arguments",
                     ));
                 }
-                let expr = self.expression()?;
-                arguments.push(expr);
+                if self.check(&TokenType::Identifier) && self.check_next(&TokenType::Colon) {
+                    let name = self.advance().expect("should be a name token").clone();
+                    self.advance();
+                    if named.iter().any(|(other, _)| other.lexeme == name.lexeme) {
+                        return Err(ReefError::reef_error_at_line(
+                            &name,
+                            "duplicate named argument",
+                        ));
+                    }
+                    named.push((name, self.expression()?));
+                } else if !named.is_empty() {
+                    return Err(ReefError::reef_error_at_line(
+                        self.peek().expect("should be a token here"),
+                        "positional argument can't follow named arguments",
+                    ));
+                } else {
+                    arguments.push(self.expression()?);
+                }
                 if !self.match_type(&[TokenType::Comma]) {
                     break;
                 }
@@ -440,6 +744,7 @@ impl Parser {
             callee: Box::new(callee),
             token: paren.clone(),
             arguments,
+            named,
         })
     }
 
@@ -448,6 +753,14 @@ impl Parser {
         loop {
             if self.match_type(&[TokenType::LeftParen]) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_type(&[TokenType::Dot]) {
+                let name = self
+                    .consume(TokenType::Identifier, "expect property name after '.'")?
+                    .clone();
+                expr = ExprKind::Get {
+                    object: Box::new(expr),
+                    name,
+                };
             } else {
                 break;
             }
@@ -502,6 +815,8 @@ impl Parser {
                 TokenType::Fun => return,
                 TokenType::Print => return,
                 TokenType::If => return,
+                TokenType::Try => return,
+                TokenType::Throw => return,
                 TokenType::Var => return,
                 _ => {}
             }
@@ -510,19 +825,23 @@ impl Parser {
     }
 
     fn primary(&mut self) -> Result<ExprKind, ReefError> {
+        let line = self.peek().map(|token| token.line).unwrap_or_default();
         if self.match_type(&[TokenType::False]) {
             return Ok(ExprKind::Literal {
                 value: Literal::Boolean(false),
+                line,
             });
         }
         if self.match_type(&[TokenType::True]) {
             return Ok(ExprKind::Literal {
                 value: Literal::Boolean(true),
+                line,
             });
         }
         if self.match_type(&[TokenType::Nil]) {
             return Ok(ExprKind::Literal {
                 value: Literal::Nil,
+                line,
             });
         }
         if self.match_type(&[TokenType::Number, TokenType::String]) {
@@ -531,12 +850,24 @@ impl Parser {
 
             return Ok(ExprKind::Literal {
                 value: literal_value,
+                line,
             });
         }
 
         if self.match_type(&[TokenType::Identifier]) {
             let name = self.previous().expect("should be tokens here").clone();
-            return Ok(ExprKind::Variable { name });
+            return Ok(ExprKind::Variable {
+                name,
+                binding: Binding::Unresolved,
+            });
+        }
+
+        if self.match_type(&[TokenType::Fun]) {
+            return self.function_expression();
+        }
+
+        if self.at_arrow_function() {
+            return self.arrow_function();
         }
 
         if self.match_type(&[TokenType::LeftParen]) {
@@ -1,31 +1,75 @@
 use crate::ast_printer::AstPrinter;
+use crate::convert::TypedFn;
 use crate::error::ReefError;
-use crate::interpreter::Interpreter;
+use crate::expr::Value;
+use crate::formatter::Formatter;
+use crate::func::{Arity, HostFunction};
+use crate::interpreter::{DEFAULT_MAX_CALL_DEPTH, Interpreter};
+use crate::linter::{LintConfig, LintLevel, Linter};
+use crate::module::FileLoader;
+use crate::optimizer::Optimizer;
 use crate::parser::Parser;
 use crate::scanner::Scanner;
 use crate::stmt::StmtKind;
+use crate::vm::{CompiledFunction, Compiler, Vm, disasm, serialize};
 use std::fs;
 use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+/// what `reef run` executes the parsed program on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    TreeWalk,
+    Vm,
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> Option<Backend> {
+        match name {
+            "tree" => Some(Backend::TreeWalk),
+            "vm" => Some(Backend::Vm),
+            _ => None,
+        }
+    }
+}
 
 pub struct Reef {
     had_error: bool,
     had_runtime_error: bool,
+    backend: Backend,
+    gc_stress: bool,
+    gc_stats: bool,
+    optimize: bool,
+    max_call_depth: usize,
+    // host natives, defined in every interpreter this runs
+    natives: Vec<Rc<HostFunction>>,
+    // file being run, which its imports are relative to
+    script: Option<String>,
 }
 /*
   Extended Backus-Naur Form (ebnf)
 
-  program       -> declaration* EOF ;
+  program       -> ( import_decl | declaration )* EOF ;
+
+  import_decl   -> "import" STRING "as" IDENTIFIER ";"
+                | "from" STRING "import" IDENTIFIER ( "," IDENTIFIER )* ";" ;
 
   declaration   -> fun_decl | var_decl | statement ;
   fun_decl      -> "fun" function ;
   function      -> IDENTIFIER "(" parameters ")" block;
-  parameters    -> IDENTIFIER ("," IDENTIFIER)* ;
+  parameters    -> parameter ("," parameter)* ("," "..." IDENTIFIER)? | "..." IDENTIFIER ;
+  parameter     -> IDENTIFIER ("=" expression)? ;
   var_decl      -> "var" IDENTIFIER ("=" expression)? ";" ;
 
   statement     -> epxr_stmt
                 | forStmt
                 | if_stmt
                 | print_stmt
+                | return_stmt
+                | throw_stmt
+                | try_stmt
                 | while_stmt
                 | block ;
 
@@ -44,9 +88,16 @@ pub struct Reef {
 
   print_stmt    -> "print" expression ";"
 
+  return_stmt   -> "return" expression? ";"
+
+  throw_stmt    -> "throw" expression ";"
+
+  try_stmt      -> "try" block ( "catch" "(" IDENTIFIER ")" block )?
+                ( "finally" block )? ;  // at least one of the two
+
   expression    -> assignment;
 
-  assignment    -> IDENTIFIER "=" assignment
+  assignment    -> ( call "." )? IDENTIFIER "=" assignment
                 | logic_or ;
 
   logic_or      -> logic_and ( "or" logic_and )* ;
@@ -63,11 +114,15 @@ pub struct Reef {
 
   unary         -> ("!" | "-") unary | call ;
 
-  call          -> primary ( "(" arguments ")" )* ;
+  call          -> primary ( "(" arguments ")" | "." IDENTIFIER )* ;
+
+  arguments     -> argument ("," argument )* ;
 
-  arguments     -> expression ("," expression )* ;
+  argument      -> (IDENTIFIER ":")? expression ;
 
-  primary       -> NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")" | IDENTIFIER ;
+  primary       -> NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")" | IDENTIFIER
+                | "fun" "(" parameters? ")" block
+                | "(" parameters? ")" "=>" ( expression | block ) ;
 
 
 */
@@ -77,9 +132,64 @@ impl Reef {
         Reef {
             had_error: false,
             had_runtime_error: false,
+            backend: Backend::default(),
+            gc_stress: false,
+            gc_stats: false,
+            optimize: false,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            natives: Vec::new(),
+            script: None,
         }
     }
 
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// exposes a rust closure to scripts as the global function `name`,
+    /// on either backend. see `Interpreter::register_fn`
+    pub fn register_fn<F>(&mut self, name: &str, arity: impl Into<Arity>, func: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Value>) -> Result<Value, ReefError> + 'static,
+    {
+        self.natives.push(Rc::new(HostFunction {
+            name: name.into(),
+            arity: arity.into(),
+            func: Box::new(func),
+        }));
+    }
+
+    /// exposes an ordinary rust function to scripts, on either backend.
+    /// see `Interpreter::register_typed`
+    pub fn register_typed<Args, F: TypedFn<Args>>(&mut self, name: &str, func: F) {
+        let owner: Rc<str> = name.into();
+        self.register_fn(name, F::ARITY, move |interpreter, arguments| {
+            func.call_typed(arguments).unwrap_or_else(|message| {
+                Err(interpreter.host_error(&format!("{}: {}", owner, message)))
+            })
+        });
+    }
+
+    /// calls a script can nest before it fails with "stack overflow"
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// folds constants and drops dead branches before running
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    /// collects garbage on every allocation in the tree-walker
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.gc_stress = stress;
+    }
+
+    /// prints collector statistics to stderr once a tree-walker run ends
+    pub fn set_gc_stats(&mut self, stats: bool) {
+        self.gc_stats = stats;
+    }
+
     pub fn run(&mut self, text: &str) -> Result<(), ReefError> {
         let mut scanner = Scanner::new(text.to_string());
 
@@ -105,24 +215,196 @@ impl Reef {
         interpreter.interpret(stmts)?;
         Ok(())
     }
+    fn parse(&mut self, text: &str) -> Result<Vec<StmtKind>, ReefError> {
+        let mut scanner = Scanner::new(text.to_string());
+        let tokens = scanner.scan_tokens();
+        if let Some((_, _, error)) = scanner.errors().first() {
+            return Err(error.clone());
+        }
+        let stmts = Parser::new(tokens).parse()?;
+        if self.optimize {
+            return Ok(Optimizer::optimize(stmts));
+        }
+        Ok(stmts)
+    }
+    fn compile(&mut self, text: &str) -> Result<Rc<CompiledFunction>, ReefError> {
+        Compiler::compile(&self.parse(text)?)
+    }
+    /// scans, parses and runs a script on the selected backend, without
+    /// the token and ast dump `run` prints
+    pub fn execute(&mut self, text: &str) -> Result<(), ReefError> {
+        match self.backend {
+            Backend::TreeWalk => {
+                let stmts = self.parse(text)?;
+                let mut interpreter = Interpreter::new();
+                interpreter.set_gc_stress(self.gc_stress);
+                interpreter.set_max_call_depth(self.max_call_depth);
+                for native in &self.natives {
+                    interpreter.define_global(&native.name, Value::Callable(native.clone()));
+                }
+                interpreter.set_module_loader(Box::new(FileLoader::from_env()));
+                if let Some(script) = &self.script {
+                    interpreter.set_script_path(script);
+                }
+                let result = interpreter.interpret(stmts);
+                if self.gc_stats {
+                    eprintln!("{}", interpreter.gc_stats());
+                }
+                result
+            }
+            Backend::Vm => {
+                let script = self.compile(text)?;
+                self.vm().interpret(script)
+            }
+        }
+    }
+    fn vm(&self) -> Vm {
+        let mut vm = Vm::new();
+        vm.set_max_call_depth(self.max_call_depth);
+        for native in &self.natives {
+            vm.define_global(&native.name, Value::Callable(native.clone()));
+        }
+        vm
+    }
+    // source or compiled bytecode, told apart by the magic number
+    fn load_program(&mut self, filename: &str) -> Result<Rc<CompiledFunction>, ReefError> {
+        let bytes = fs::read(filename).unwrap_or_else(|_| {
+            eprintln!("Failed to read file {}", filename);
+            std::process::exit(66)
+        });
+        if serialize::is_bytecode(&bytes) {
+            return serialize::decode(&bytes);
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => self.compile(&text),
+            Err(_) => Err(ReefError::reef_general_error(&format!(
+                "{} is neither reef source nor bytecode",
+                filename
+            ))),
+        }
+    }
+    pub fn run_script(&mut self, filename: &str) {
+        let bytes = fs::read(filename).unwrap_or_else(|_| {
+            eprintln!("Failed to read file {}", filename);
+            std::process::exit(66)
+        });
+        self.script = Some(filename.to_string());
+        // bytecode only runs on the vm, whatever backend was asked for
+        let result = if serialize::is_bytecode(&bytes) {
+            serialize::decode(&bytes).and_then(|script| self.vm().interpret(script))
+        } else {
+            self.execute(&String::from_utf8_lossy(&bytes))
+        };
+        if let Err(e) = result {
+            self.report_error(&e);
+        }
+        self.exit_on_error();
+    }
+    /// prints the parsed tree, after the optimizer if it's enabled
+    pub fn dump_ast(&mut self, filename: &str) {
+        let text = fs::read_to_string(filename).unwrap_or_else(|_| {
+            eprintln!("Failed to read file {}", filename);
+            std::process::exit(66)
+        });
+        match self.parse(&text) {
+            Ok(stmts) => stmts
+                .iter()
+                .for_each(|stmt| println!("{}", AstPrinter::print_stmt(stmt))),
+            Err(e) => self.report_error(&e),
+        }
+        self.exit_on_error();
+    }
+    pub fn compile_file(&mut self, filename: &str, output: Option<&str>) {
+        let output = match output {
+            Some(output) => output.to_string(),
+            None => Path::new(filename)
+                .with_extension("reefc")
+                .to_string_lossy()
+                .into_owned(),
+        };
+        match self.load_program(filename) {
+            Ok(script) => {
+                if let Err(e) = fs::write(&output, serialize::encode(&script)) {
+                    eprintln!("Failed to write file {}: {}", output, e);
+                    std::process::exit(74)
+                }
+            }
+            Err(e) => self.report_error(&e),
+        }
+        self.exit_on_error();
+    }
+    pub fn disassemble_file(&mut self, filename: &str) {
+        match self.load_program(filename) {
+            Ok(script) => print!("{}", disasm::disassemble(&script)),
+            Err(e) => self.report_error(&e),
+        }
+        self.exit_on_error();
+    }
+    fn exit_on_error(&self) {
+        if self.had_error {
+            std::process::exit(65)
+        }
+        if self.had_runtime_error {
+            std::process::exit(70)
+        }
+    }
     pub fn run_file(&mut self, filename: &str) {
         let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
             eprintln!("Failed to read file {}", filename);
             String::new()
         });
         if !file_contents.is_empty() {
-            match self.run(&file_contents) {
-                Ok(()) => {}
-                Err(e) => println!("failed to run reef file: {:?}", e),
-            };
+            if let Err(e) = self.run(&file_contents) {
+                self.report_error(&e);
+            }
         } else {
             println!("EOF  null");
         }
-        if self.had_error {
-            std::process::exit(65)
+        self.exit_on_error();
+    }
+    pub fn format_file(&mut self, filename: &str, check: bool) {
+        let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
+            eprintln!("Failed to read file {}", filename);
+            std::process::exit(66)
+        });
+        let formatted = match Formatter::format(&file_contents) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                self.report_error(&e);
+                std::process::exit(65)
+            }
+        };
+        if formatted == file_contents {
+            return;
         }
-        if self.had_runtime_error {
-            std::process::exit(70)
+        if check {
+            eprintln!("{} is not formatted", filename);
+            std::process::exit(1)
+        }
+        if let Err(e) = fs::write(filename, formatted) {
+            eprintln!("Failed to write file {}: {}", filename, e);
+            std::process::exit(74)
+        }
+    }
+    pub fn lint_file(&mut self, filename: &str, config: LintConfig) {
+        let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
+            eprintln!("Failed to read file {}", filename);
+            std::process::exit(66)
+        });
+        let tokens = Scanner::new(file_contents).scan_tokens();
+        let stmts = match Parser::new(tokens).parse() {
+            Ok(stmts) => stmts,
+            Err(e) => {
+                self.report_error(&e);
+                std::process::exit(65)
+            }
+        };
+        let diagnostics = Linter::new(config).lint(&stmts);
+        for diagnostic in &diagnostics {
+            eprintln!("{}: {}", filename, diagnostic);
+        }
+        if diagnostics.iter().any(|d| d.level == LintLevel::Deny) {
+            std::process::exit(1)
         }
     }
     pub fn run_repl(&mut self) -> io::Result<()> {
@@ -141,11 +423,16 @@ impl Reef {
         }
         Ok(())
     }
+    // the error was printed where it was raised, this only picks the exit code
     fn report_error(&mut self, error: &ReefError) {
-        eprintln!("{:?}", error);
         match error {
             ReefError::ParseError { .. } => self.had_error = true,
-            ReefError::RuntimeError { .. } => self.had_runtime_error = true,
+            ReefError::RuntimeError { .. }
+            | ReefError::Return(_)
+            | ReefError::TailCall(..)
+            | ReefError::LimitExceeded(_)
+            | ReefError::Thrown(..)
+            | ReefError::Disconnected(_) => self.had_runtime_error = true,
         }
     }
 }
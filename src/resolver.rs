@@ -0,0 +1,260 @@
+use std::rc::Rc;
+
+use crate::{
+    Literal, Token,
+    expr::{Binding, ExprKind},
+    interner::Interner,
+    stmt::StmtKind,
+};
+
+/// static pass run before interpreting: binds every variable use to the
+/// scope depth and slot its declaration will occupy at runtime, so local
+/// reads skip the name lookup. scopes mirror the interpreter's
+/// environments, one per block and one per function call. identifiers and
+/// string literals are interned on the way
+pub struct Resolver<'a> {
+    // names declared so far in each local scope, in slot order
+    scopes: Vec<Vec<Rc<str>>>,
+    interner: &'a mut Interner,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interner: &'a mut Interner) -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            interner,
+        }
+    }
+
+    fn intern(&mut self, name: &mut Token) {
+        name.lexeme = self.interner.intern(&name.lexeme);
+    }
+
+    pub fn resolve(&mut self, stmts: &mut [StmtKind]) {
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn declare(&mut self, name: &mut Token) {
+        self.intern(name);
+        // top level names are globals, looked up by name
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Rc::clone(&name.lexeme));
+        }
+    }
+
+    fn binding(&mut self, name: &mut Token) -> Binding {
+        self.intern(name);
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            // a redeclared name resolves to its latest slot. everything in
+            // scope is interned, so comparing pointers is enough
+            if let Some(slot) = scope
+                .iter()
+                .rposition(|local| Rc::ptr_eq(local, &name.lexeme))
+            {
+                return Binding::Local { depth, slot };
+            }
+        }
+        Binding::Global
+    }
+
+    fn resolve_block(&mut self, stmts: &mut [StmtKind]) {
+        self.scopes.push(Vec::new());
+        self.resolve(stmts);
+        self.scopes.pop();
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut StmtKind) {
+        match stmt {
+            StmtKind::Print { expr } | StmtKind::Expression { expr } => self.resolve_expr(expr),
+            StmtKind::Var { name, initializer } => {
+                // the initializer runs before the name is defined
+                self.resolve_expr(initializer);
+                self.declare(name);
+            }
+            StmtKind::Block { statements } => self.resolve_block(statements),
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+            }
+            StmtKind::Function {
+                name,
+                parameters,
+                defaults,
+                rest,
+                body,
+            } => {
+                self.declare(name);
+                self.resolve_function(parameters, defaults, rest, body);
+            }
+            StmtKind::Return { value, .. } => self.resolve_expr(value),
+            StmtKind::Import { alias, names, .. } => {
+                for name in alias.iter_mut().chain(names) {
+                    self.declare(name);
+                }
+            }
+            StmtKind::Throw { value, .. } => self.resolve_expr(value),
+            StmtKind::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                self.resolve_block(body);
+                if let Some((name, handler)) = catch {
+                    // the caught error is the first name in the handler's scope
+                    self.scopes.push(Vec::new());
+                    self.declare(name);
+                    self.resolve(handler);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.resolve_block(finally);
+                }
+            }
+            StmtKind::Error { .. } => {}
+        }
+    }
+
+    fn resolve_function(
+        &mut self,
+        parameters: &mut [Token],
+        defaults: &mut [ExprKind],
+        rest: &mut Option<Token>,
+        body: &mut [StmtKind],
+    ) {
+        // parameters and the body share the call's environment. a default
+        // runs once the parameters before it are defined
+        self.scopes.push(Vec::new());
+        let required = parameters.len() - defaults.len();
+        for (index, param) in parameters.iter_mut().enumerate() {
+            if let Some(default) = index
+                .checked_sub(required)
+                .and_then(|index| defaults.get_mut(index))
+            {
+                self.resolve_expr(default);
+            }
+            self.declare(param);
+        }
+        if let Some(rest) = rest {
+            self.declare(rest);
+        }
+        self.resolve(body);
+        self.scopes.pop();
+    }
+
+    fn resolve_expr(&mut self, expr: &mut ExprKind) {
+        match expr {
+            ExprKind::Assign {
+                name,
+                value,
+                binding,
+            } => {
+                self.resolve_expr(value);
+                *binding = self.binding(name);
+            }
+            ExprKind::Variable { name, binding } => *binding = self.binding(name),
+            ExprKind::Binary { left, right, .. } | ExprKind::Logical { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            ExprKind::Call {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                self.resolve_expr(callee);
+                for arg in arguments
+                    .iter_mut()
+                    .chain(named.iter_mut().map(|(_, arg)| arg))
+                {
+                    self.resolve_expr(arg);
+                }
+            }
+            ExprKind::Function {
+                parameters,
+                defaults,
+                rest,
+                body,
+                ..
+            } => self.resolve_function(parameters, defaults, rest, body),
+            ExprKind::Get { object, .. } => self.resolve_expr(object),
+            ExprKind::Set { object, value, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(value);
+            }
+            ExprKind::Grouping { expression } => self.resolve_expr(expression),
+            ExprKind::Unary { right, .. } => self.resolve_expr(right),
+            ExprKind::Literal {
+                value: Literal::String(text),
+                ..
+            } => *text = self.interner.intern(text),
+            ExprKind::Literal { .. }
+            | ExprKind::Super { .. }
+            | ExprKind::This { .. }
+            | ExprKind::None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Resolver;
+    use crate::expr::{Binding, ExprKind};
+    use crate::interner::Interner;
+    use crate::stmt::StmtKind;
+    use crate::{Parser, Scanner};
+
+    fn printed_binding(stmt: &StmtKind) -> Binding {
+        match stmt {
+            StmtKind::Print {
+                expr: ExprKind::Variable { binding, .. },
+            } => *binding,
+            _ => panic!("expected `print <variable>;`"),
+        }
+    }
+
+    #[test]
+    fn test_bindings() {
+        let source = "var g = 1;\n{ var a = 1; var b = 2; { print b; print g; } fun f(x) { print x; print a; } }";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let mut stmts = Parser::new(tokens).parse().unwrap();
+        Resolver::new(&mut Interner::new()).resolve(&mut stmts);
+
+        let StmtKind::Block { statements } = &stmts[1] else {
+            panic!("expected a block");
+        };
+        let StmtKind::Block { statements: inner } = &statements[2] else {
+            panic!("expected a block");
+        };
+        assert_eq!(
+            printed_binding(&inner[0]),
+            Binding::Local { depth: 1, slot: 1 }
+        );
+        assert_eq!(printed_binding(&inner[1]), Binding::Global);
+        let StmtKind::Function { body, .. } = &statements[3] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            printed_binding(&body[0]),
+            Binding::Local { depth: 0, slot: 0 }
+        );
+        assert_eq!(
+            printed_binding(&body[1]),
+            Binding::Local { depth: 1, slot: 0 }
+        );
+    }
+}
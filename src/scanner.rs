@@ -2,43 +2,63 @@ use std::collections::HashMap;
 
 use crate::{
     error::ReefError,
-    token::{Literal, Token, TokenType},
+    token::{Literal, Token, TokenType, Trivia, TriviaKind},
 };
 
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("and", TokenType::And),
+    ("catch", TokenType::Catch),
+    ("class", TokenType::Class),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("finally", TokenType::Finally),
+    ("for", TokenType::For),
+    ("fun", TokenType::Fun),
+    ("if", TokenType::If),
+    ("import", TokenType::Import),
+    ("nil", TokenType::Nil),
+    ("or", TokenType::Or),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("throw", TokenType::Throw),
+    ("true", TokenType::True),
+    ("try", TokenType::Try),
+    ("var", TokenType::Var),
+    ("while", TokenType::While),
+];
+
 pub struct Scanner {
     source: String,
     tokens: Vec<Token>,
+    trivia: Vec<Trivia>,
+    line_has_token: bool,
+    line_has_content: bool,
+    errors: Vec<(usize, usize, ReefError)>,
     line: usize,
+    line_start: usize,
     start: usize,
+    start_column: usize,
     current: usize,
     keywords: HashMap<&'static str, TokenType>,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
-        let mut keywords = HashMap::new();
-        keywords.insert("and", TokenType::And);
-        keywords.insert("class", TokenType::Class);
-        keywords.insert("else", TokenType::Else);
-        keywords.insert("false", TokenType::False);
-        keywords.insert("for", TokenType::For);
-        keywords.insert("fun", TokenType::Fun);
-        keywords.insert("if", TokenType::If);
-        keywords.insert("nil", TokenType::Nil);
-        keywords.insert("or", TokenType::Or);
-        keywords.insert("print", TokenType::Print);
-        keywords.insert("return", TokenType::Return);
-        keywords.insert("super", TokenType::Super);
-        keywords.insert("this", TokenType::This);
-        keywords.insert("true", TokenType::True);
-        keywords.insert("var", TokenType::Var);
-        keywords.insert("while", TokenType::While);
+        let keywords = KEYWORDS.iter().copied().collect();
 
         Scanner {
             source,
             tokens: Vec::new(),
+            trivia: Vec::new(),
+            line_has_token: false,
+            line_has_content: false,
+            errors: Vec::new(),
             line: 1,
+            line_start: 0,
             start: 0,
+            start_column: 0,
             current: 0,
             keywords,
         }
@@ -46,14 +66,34 @@ impl Scanner {
 
     fn add_token(&mut self, token_type: TokenType) {
         let lexeme = self.source[self.start..self.current].to_string();
-        self.tokens
-            .push(Token::new(token_type, lexeme, None, self.line));
+        let mut token = Token::new(token_type, lexeme, None, self.line);
+        token.column = self.start_column;
+        self.tokens.push(token);
+        self.line_has_token = true;
+        self.line_has_content = true;
     }
 
     fn add_token_with_literal(&mut self, token_type: TokenType, literal: Literal) {
         let lexeme = self.source[self.start..self.current].to_string();
-        let token = Token::new(token_type, lexeme, Some(literal), self.line);
+        let mut token = Token::new(token_type, lexeme, Some(literal), self.line);
+        token.column = self.start_column;
         self.tokens.push(token);
+        self.line_has_token = true;
+        self.line_has_content = true;
+    }
+
+    fn add_error(&mut self, message: &str) {
+        let error = ReefError::reef_error(self.line, message);
+        self.errors.push((self.line, self.start_column, error));
+    }
+
+    fn add_trivia(&mut self, kind: TriviaKind, trailing: bool) {
+        self.trivia.push(Trivia {
+            kind,
+            line: self.line,
+            token_index: self.tokens.len(),
+            trailing,
+        });
     }
 
     fn advance(&mut self) -> char {
@@ -71,7 +111,15 @@ impl Scanner {
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
             ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.current += 2;
+                    self.add_token(TokenType::Ellipsis);
+                } else {
+                    self.add_token(TokenType::Dot);
+                }
+            }
+            ':' => self.add_token(TokenType::Colon),
             ';' => self.add_token(TokenType::Semicolon),
             '*' => self.add_token(TokenType::Star),
             '!' => {
@@ -84,6 +132,8 @@ impl Scanner {
             '=' => {
                 if self.match_next_char('=') {
                     self.add_token(TokenType::EqualEqual);
+                } else if self.match_next_char('>') {
+                    self.add_token(TokenType::Arrow);
                 } else {
                     self.add_token(TokenType::Equal);
                 }
@@ -107,6 +157,10 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    let comment = self.source[self.start..self.current].trim_end().to_string();
+                    let trailing = self.line_has_token;
+                    self.add_trivia(TriviaKind::Comment(comment), trailing);
+                    self.line_has_content = true;
                 } else {
                     self.add_token(TokenType::Slash);
                 }
@@ -115,7 +169,13 @@ impl Scanner {
             '\r' => {}
             '\t' => {}
             '\n' => {
+                if !self.line_has_content {
+                    self.add_trivia(TriviaKind::BlankLine, false);
+                }
                 self.line += 1;
+                self.line_start = self.current;
+                self.line_has_token = false;
+                self.line_has_content = false;
             }
             '"' => self.string(),
             _ => {
@@ -124,7 +184,7 @@ impl Scanner {
                 } else if self.is_alpha(c) {
                     self.identifier();
                 } else {
-                    ReefError::reef_error(self.line, "unexpected character");
+                    self.add_error("unexpected character");
                 }
             }
         }
@@ -175,12 +235,13 @@ impl Scanner {
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' {
                 self.line += 1;
+                self.line_start = self.current + 1;
             }
             self.advance();
         }
 
         if self.is_at_end() {
-            ReefError::reef_error(self.line, "Unterminated string.");
+            self.add_error("Unterminated string.");
             return;
         }
 
@@ -188,7 +249,7 @@ impl Scanner {
         self.advance();
 
         let str_val = self.source[self.start + 1..self.current - 1].to_string();
-        self.add_token_with_literal(TokenType::String, Literal::String(str_val));
+        self.add_token_with_literal(TokenType::String, Literal::String(str_val.into()));
     }
 
     fn match_next_char(&mut self, expected: char) -> bool {
@@ -222,14 +283,25 @@ impl Scanner {
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_column = self.start - self.line_start;
             let c = self.advance();
             self.handle_token(&c);
         }
-        self.tokens
-            .push(Token::new(TokenType::Eof, "".to_string(), None, self.line));
+        self.start = self.current;
+        self.start_column = self.current - self.line_start;
+        self.add_token(TokenType::Eof);
         self.tokens.clone()
     }
 
+    pub fn trivia(&self) -> &[Trivia] {
+        &self.trivia
+    }
+
+    /// lexical errors as (line, column, error)
+    pub fn errors(&self) -> &[(usize, usize, ReefError)] {
+        &self.errors
+    }
+
     pub fn print_info(&self) {
         println!("printing tokens:");
         for tok in &self.tokens {
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::{Token, environment::Environment, error::ReefError, expr::ExprKind};
 
 #[derive(Debug, Clone)]
@@ -30,10 +32,55 @@ pub enum StmtKind {
     Function {
         name: Token,
         parameters: Vec<Token>,
+        // values of the trailing parameters, for calls that leave them out
+        defaults: Vec<ExprKind>,
+        // `...name`, gathering arguments past the last parameter
+        rest: Option<Token>,
+        body: Vec<StmtKind>,
+    },
+    Return {
+        keyword: Token,
+        value: ExprKind,
+    },
+    // `import "path" as alias;` binds the module, `from "path" import a, b;`
+    // binds the names it exports
+    Import {
+        keyword: Token,
+        path: Rc<str>,
+        alias: Option<Token>,
+        names: Vec<Token>,
+    },
+    Throw {
+        keyword: Token,
+        value: ExprKind,
+    },
+    // `try { } catch (name) { } finally { }`, with a catch, a finally or
+    // both
+    Try {
+        keyword: Token,
         body: Vec<StmtKind>,
+        catch: Option<(Token, Vec<StmtKind>)>,
+        finally: Option<Vec<StmtKind>>,
     },
 }
 
+impl StmtKind {
+    /// line the statement starts on
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            StmtKind::Print { expr } | StmtKind::Expression { expr } => expr.line(),
+            StmtKind::Var { name, .. } | StmtKind::Function { name, .. } => Some(name.line),
+            StmtKind::Block { statements } => statements.iter().find_map(StmtKind::line),
+            StmtKind::If { condition, .. } | StmtKind::While { condition, .. } => condition.line(),
+            StmtKind::Return { keyword, .. }
+            | StmtKind::Import { keyword, .. }
+            | StmtKind::Throw { keyword, .. }
+            | StmtKind::Try { keyword, .. } => Some(keyword.line),
+            StmtKind::Error { .. } => None,
+        }
+    }
+}
+
 pub struct Stmt {
     stmt: StmtKind,
 }
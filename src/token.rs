@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenType {
     LeftParen,
@@ -5,7 +7,10 @@ pub enum TokenType {
     LeftBrace,
     RightBrace,
     Comma,
+    Colon,
     Dot,
+    // `...` before a rest parameter
+    Ellipsis,
     Minus,
     Plus,
     Semicolon,
@@ -21,6 +26,7 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    Arrow,
 
     // literals.
     Identifier,
@@ -29,19 +35,24 @@ pub enum TokenType {
 
     // keywords.
     And,
+    Catch,
     Class,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -50,7 +61,7 @@ pub enum TokenType {
 
 #[derive(Debug, Clone)]
 pub enum Literal {
-    String(String),
+    String(Rc<str>),
     Number(f64),
     Boolean(bool),
     Nil,
@@ -59,23 +70,43 @@ pub enum Literal {
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Rc<str>,
     pub literal: Option<Literal>,
     pub line: usize,
+    pub column: usize,
 }
 
 impl Token {
     pub fn new(
         token_type: TokenType,
-        lexeme: String,
+        lexeme: impl Into<Rc<str>>,
         literal: Option<Literal>,
         line: usize,
     ) -> Self {
         Token {
             token_type,
-            lexeme,
+            lexeme: lexeme.into(),
             literal,
             line,
+            column: 0,
         }
     }
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriviaKind {
+    Comment(String),
+    BlankLine,
+}
+
+/// source text the parser never sees (comments, blank lines), kept so
+/// tooling like the formatter can reproduce the file losslessly
+#[derive(Debug, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub line: usize,
+    // index of the token this trivia comes before
+    pub token_index: usize,
+    // comment sits on the same line as the preceding token
+    pub trailing: bool,
+}
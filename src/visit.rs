@@ -0,0 +1,182 @@
+use crate::{Token, expr::ExprKind, stmt::StmtKind};
+
+/// how a name came to be declared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclarationKind {
+    Variable,
+    Parameter,
+    Function,
+    // the error a `catch` binds
+    Caught,
+}
+
+/// read-only pass over the statement tree that follows reef's scoping:
+/// one scope per block, per function call and per `catch` handler, with
+/// a default running once the parameters before it are defined. names
+/// declared outside every scope are globals. each `visit_` method walks
+/// its children by default, so an override that calls the matching
+/// `walk_` function keeps the traversal going
+pub trait Visitor: Sized {
+    fn begin_scope(&mut self);
+    fn end_scope(&mut self);
+    fn declare(&mut self, name: &Token, kind: DeclarationKind);
+    /// a variable read, or written when `assigned`
+    fn reference(&mut self, name: &Token, assigned: bool);
+
+    fn visit_statements(&mut self, stmts: &[StmtKind]) {
+        walk_statements(self, stmts);
+    }
+
+    fn visit_stmt(&mut self, stmt: &StmtKind) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: &Token,
+        parameters: &[Token],
+        defaults: &[ExprKind],
+        rest: Option<&Token>,
+        body: &[StmtKind],
+    ) {
+        self.declare(name, DeclarationKind::Function);
+        walk_function(self, parameters, defaults, rest, body);
+    }
+
+    fn visit_expr(&mut self, expr: &ExprKind) {
+        walk_expr(self, expr);
+    }
+}
+
+pub fn walk_statements(visitor: &mut impl Visitor, stmts: &[StmtKind]) {
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+fn walk_scope(visitor: &mut impl Visitor, stmts: &[StmtKind]) {
+    visitor.begin_scope();
+    visitor.visit_statements(stmts);
+    visitor.end_scope();
+}
+
+pub fn walk_stmt(visitor: &mut impl Visitor, stmt: &StmtKind) {
+    match stmt {
+        StmtKind::Print { expr } | StmtKind::Expression { expr } => visitor.visit_expr(expr),
+        StmtKind::Var { name, initializer } => {
+            // the initializer runs before the name is defined
+            visitor.visit_expr(initializer);
+            visitor.declare(name, DeclarationKind::Variable);
+        }
+        StmtKind::Block { statements } => walk_scope(visitor, statements),
+        StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt(else_branch);
+            }
+        }
+        StmtKind::While { condition, body } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(body);
+        }
+        StmtKind::Function {
+            name,
+            parameters,
+            defaults,
+            rest,
+            body,
+        } => visitor.visit_function_declaration(name, parameters, defaults, rest.as_ref(), body),
+        StmtKind::Return { value, .. } | StmtKind::Throw { value, .. } => visitor.visit_expr(value),
+        StmtKind::Try {
+            body,
+            catch,
+            finally,
+            ..
+        } => {
+            walk_scope(visitor, body);
+            if let Some((name, handler)) = catch {
+                // the caught error is the first name in the handler's scope
+                visitor.begin_scope();
+                visitor.declare(name, DeclarationKind::Caught);
+                visitor.visit_statements(handler);
+                visitor.end_scope();
+            }
+            if let Some(finally) = finally {
+                walk_scope(visitor, finally);
+            }
+        }
+        // imports are only allowed at the top level, where names are globals
+        StmtKind::Import { .. } | StmtKind::Error { .. } => {}
+    }
+}
+
+/// parameters and the body share the call's scope
+pub fn walk_function(
+    visitor: &mut impl Visitor,
+    parameters: &[Token],
+    defaults: &[ExprKind],
+    rest: Option<&Token>,
+    body: &[StmtKind],
+) {
+    visitor.begin_scope();
+    let required = parameters.len() - defaults.len();
+    for (index, param) in parameters.iter().enumerate() {
+        if let Some(default) = index.checked_sub(required).map(|index| &defaults[index]) {
+            visitor.visit_expr(default);
+        }
+        visitor.declare(param, DeclarationKind::Parameter);
+    }
+    if let Some(rest) = rest {
+        visitor.declare(rest, DeclarationKind::Parameter);
+    }
+    visitor.visit_statements(body);
+    visitor.end_scope();
+}
+
+pub fn walk_expr(visitor: &mut impl Visitor, expr: &ExprKind) {
+    match expr {
+        ExprKind::Assign { name, value, .. } => {
+            visitor.visit_expr(value);
+            visitor.reference(name, true);
+        }
+        ExprKind::Variable { name, .. } => visitor.reference(name, false),
+        ExprKind::Binary { left, right, .. } | ExprKind::Logical { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        ExprKind::Call {
+            callee,
+            arguments,
+            named,
+            ..
+        } => {
+            visitor.visit_expr(callee);
+            for arg in arguments.iter().chain(named.iter().map(|(_, arg)| arg)) {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Function {
+            parameters,
+            defaults,
+            rest,
+            body,
+            ..
+        } => walk_function(visitor, parameters, defaults, rest.as_ref(), body),
+        ExprKind::Get { object, .. } => visitor.visit_expr(object),
+        ExprKind::Set { object, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(value);
+        }
+        ExprKind::Grouping { expression } => visitor.visit_expr(expression),
+        ExprKind::Unary { right, .. } => visitor.visit_expr(right),
+        ExprKind::Literal { .. }
+        | ExprKind::Super { .. }
+        | ExprKind::This { .. }
+        | ExprKind::None => {}
+    }
+}
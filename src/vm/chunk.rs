@@ -0,0 +1,180 @@
+use crate::expr::Value;
+
+/// one byte instructions. operands follow inline: constant and global
+/// indexes and jump offsets are two bytes (big endian), local slots,
+/// upvalue indexes and argument counts are one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Return,
+    // call that replaces the current frame, for `return f(x);`
+    TailCall,
+    // host object properties, named by a string constant
+    GetProperty,
+    SetProperty,
+    // makes a closure of the function constant, capturing its upvalues
+    Closure,
+    GetUpvalue,
+    SetUpvalue,
+    // moves a captured local off the stack into its upvalue, then pops it
+    CloseUpvalue,
+}
+
+impl OpCode {
+    pub const ALL: &[OpCode] = &[
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Return,
+        OpCode::TailCall,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::Closure,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::CloseUpvalue,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+
+    /// number of operand bytes following the opcode
+    pub fn operand_len(self) -> usize {
+        match self {
+            OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::Closure
+            | OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::Loop => 2,
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::Call
+            | OpCode::TailCall => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    // source line of every byte in `code`
+    pub lines: Vec<usize>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, line: usize) {
+        self.write(op as u8, line);
+    }
+
+    pub fn write_u16(&mut self, value: u16, line: usize) {
+        self.write((value >> 8) as u8, line);
+        self.write(value as u8, line);
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    /// adds a constant, reusing an existing slot for equal numbers and strings
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        let existing = self.constants.iter().position(|c| match (c, &value) {
+            (Value::Number(a), Value::Number(b)) => a.to_bits() == b.to_bits(),
+            (Value::String(a), Value::String(b)) => a == b,
+            _ => false,
+        });
+        existing.unwrap_or_else(|| {
+            self.constants.push(value);
+            self.constants.len() - 1
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunk, OpCode};
+    use crate::expr::Value;
+
+    #[test]
+    fn test_opcode_round_trip() {
+        for op in OpCode::ALL {
+            assert_eq!(OpCode::from_byte(*op as u8), Some(*op));
+        }
+        assert_eq!(OpCode::from_byte(OpCode::ALL.len() as u8), None);
+    }
+
+    #[test]
+    fn test_constants_are_deduplicated() {
+        let mut chunk = Chunk::default();
+        let a = chunk.add_constant(Value::Number(1.0));
+        let b = chunk.add_constant(Value::String("x".into()));
+        assert_eq!(chunk.add_constant(Value::Number(1.0)), a);
+        assert_eq!(chunk.add_constant(Value::String("x".into())), b);
+        assert_eq!(chunk.constants.len(), 2);
+    }
+}
@@ -0,0 +1,602 @@
+use std::{mem, rc::Rc};
+
+use super::{CompiledFunction, Upvalue, chunk::OpCode};
+use crate::{
+    Literal, Token, TokenType,
+    error::ReefError,
+    expr::{ExprKind, Value},
+    stmt::StmtKind,
+};
+
+// local slots and upvalues are addressed with a single byte, and .reefc
+// files count upvalues in one
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 255;
+
+#[derive(Debug)]
+struct Local {
+    name: String,
+    depth: usize,
+    // a closure uses it, so it has to outlive its stack slot
+    captured: bool,
+}
+
+/// single pass compiler from the parsed ast to bytecode. top level
+/// variables are globals looked up by name, everything inside a block or
+/// function lives in a stack slot resolved here, and locals of enclosing
+/// functions are reached through upvalues
+pub struct Compiler {
+    function: CompiledFunction,
+    locals: Vec<Local>,
+    // compiler of the function this one is nested in
+    enclosing: Option<Box<Compiler>>,
+    scope_depth: usize,
+    is_script: bool,
+    line: usize,
+}
+
+impl Compiler {
+    fn new(name: &str, arity: usize, line: usize) -> Self {
+        Compiler {
+            function: CompiledFunction {
+                name: name.to_string(),
+                arity,
+                chunk: Default::default(),
+                upvalues: Vec::new(),
+            },
+            // slot 0 holds the function being called
+            locals: vec![Local {
+                name: String::new(),
+                depth: 0,
+                captured: false,
+            }],
+            enclosing: None,
+            scope_depth: 0,
+            is_script: false,
+            line,
+        }
+    }
+
+    pub fn compile(stmts: &[StmtKind]) -> Result<Rc<CompiledFunction>, ReefError> {
+        let mut compiler = Compiler::new("script", 0, 1);
+        compiler.is_script = true;
+        for stmt in stmts {
+            compiler.statement(stmt)?;
+        }
+        Ok(Rc::new(compiler.finish()))
+    }
+
+    fn finish(mut self) -> CompiledFunction {
+        self.emit(OpCode::Nil);
+        self.emit(OpCode::Return);
+        self.function
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        self.function.chunk.write_op(op, self.line);
+    }
+
+    fn emit_byte(&mut self, op: OpCode, operand: u8) {
+        self.emit(op);
+        self.function.chunk.write(operand, self.line);
+    }
+
+    fn emit_u16(&mut self, op: OpCode, operand: u16) {
+        self.emit(op);
+        self.function.chunk.write_u16(operand, self.line);
+    }
+
+    fn make_constant(&mut self, value: Value) -> Result<u16, ReefError> {
+        let index = self.function.chunk.add_constant(value);
+        u16::try_from(index)
+            .map_err(|_| ReefError::reef_error(self.line, "too many constants in one chunk"))
+    }
+
+    fn emit_constant(&mut self, value: Value) -> Result<(), ReefError> {
+        let index = self.make_constant(value)?;
+        self.emit_u16(OpCode::Constant, index);
+        Ok(())
+    }
+
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_u16(op, u16::MAX);
+        self.function.chunk.code.len() - 2
+    }
+
+    fn patch_jump(&mut self, operand: usize) -> Result<(), ReefError> {
+        let jump = self.function.chunk.code.len() - operand - 2;
+        let jump = u16::try_from(jump)
+            .map_err(|_| ReefError::reef_error(self.line, "too much code to jump over"))?;
+        self.function.chunk.code[operand..operand + 2].copy_from_slice(&jump.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) -> Result<(), ReefError> {
+        // jump back over the loop body plus this instruction
+        let offset = self.function.chunk.code.len() - loop_start + 3;
+        let offset = u16::try_from(offset)
+            .map_err(|_| ReefError::reef_error(self.line, "loop body too large"))?;
+        self.emit_u16(OpCode::Loop, offset);
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.scope_depth -= 1;
+        while let Some(local) = self.locals.pop_if(|local| local.depth > self.scope_depth) {
+            if local.captured {
+                self.emit(OpCode::CloseUpvalue);
+            } else {
+                self.emit(OpCode::Pop);
+            }
+        }
+    }
+
+    fn add_local(&mut self, name: &Token) -> Result<(), ReefError> {
+        if self.locals.len() == MAX_LOCALS {
+            return Err(ReefError::reef_error_at_line(
+                name,
+                "too many local variables in function",
+            ));
+        }
+        self.locals.push(Local {
+            name: name.lexeme.to_string(),
+            depth: self.scope_depth,
+            captured: false,
+        });
+        Ok(())
+    }
+
+    // the value to bind is already on top of the stack
+    fn define_variable(&mut self, name: &Token) -> Result<(), ReefError> {
+        if self.scope_depth > 0 {
+            return self.add_local(name);
+        }
+        let index = self.make_constant(Value::String(name.lexeme.clone()))?;
+        self.emit_u16(OpCode::DefineGlobal, index);
+        Ok(())
+    }
+
+    fn resolve_local(&self, name: &Token) -> Option<u8> {
+        self.locals
+            .iter()
+            .rposition(|local| *local.name == *name.lexeme)
+            .map(|slot| slot as u8)
+    }
+
+    // a local of some enclosing function, captured by every function
+    // between it and this one
+    fn resolve_upvalue(&mut self, name: &Token) -> Result<Option<u8>, ReefError> {
+        let Some(enclosing) = self.enclosing.as_mut() else {
+            return Ok(None);
+        };
+        if let Some(slot) = enclosing.resolve_local(name) {
+            enclosing.locals[slot as usize].captured = true;
+            return self.add_upvalue(name, true, slot).map(Some);
+        }
+        match enclosing.resolve_upvalue(name)? {
+            Some(index) => self.add_upvalue(name, false, index).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_upvalue(&mut self, name: &Token, is_local: bool, index: u8) -> Result<u8, ReefError> {
+        let upvalue = Upvalue { is_local, index };
+        let upvalues = &mut self.function.upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return Ok(existing as u8);
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            return Err(ReefError::reef_error_at_line(
+                name,
+                "too many closure variables in function",
+            ));
+        }
+        upvalues.push(upvalue);
+        Ok((upvalues.len() - 1) as u8)
+    }
+
+    fn statement(&mut self, stmt: &StmtKind) -> Result<(), ReefError> {
+        if let Some(line) = stmt.line() {
+            self.line = line;
+        }
+        match stmt {
+            StmtKind::Expression { expr } => {
+                self.expression(expr)?;
+                self.emit(OpCode::Pop);
+            }
+            StmtKind::Print { expr } => {
+                self.expression(expr)?;
+                self.emit(OpCode::Print);
+            }
+            StmtKind::Var { name, initializer } => {
+                // compiled before the name exists, so `var a = a;` reads
+                // the outer `a` like the tree-walker does
+                self.expression(initializer)?;
+                self.define_variable(name)?;
+            }
+            StmtKind::Block { statements } => {
+                self.begin_scope();
+                for stmt in statements {
+                    self.statement(stmt)?;
+                }
+                self.end_scope();
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition)?;
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.statement(then_branch)?;
+                let else_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(then_jump)?;
+                self.emit(OpCode::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(else_jump)?;
+            }
+            StmtKind::While { condition, body } => {
+                let loop_start = self.function.chunk.code.len();
+                self.expression(condition)?;
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.statement(body)?;
+                self.emit_loop(loop_start)?;
+                self.patch_jump(exit_jump)?;
+                self.emit(OpCode::Pop);
+            }
+            StmtKind::Function {
+                name,
+                parameters,
+                defaults,
+                rest,
+                body,
+            } => {
+                // a local function is in scope in its own body, so it can
+                // call itself through an upvalue
+                if self.scope_depth > 0 {
+                    self.add_local(name)?;
+                }
+                let function = self.function(name, parameters, defaults, rest.as_ref(), body)?;
+                self.emit_closure(function)?;
+                if self.scope_depth == 0 {
+                    self.define_variable(name)?;
+                }
+            }
+            StmtKind::Return { keyword, value } => {
+                if self.is_script {
+                    return Err(ReefError::reef_error_at_line(
+                        keyword,
+                        "can't return from top-level code",
+                    ));
+                }
+                match value {
+                    ExprKind::Call {
+                        callee,
+                        token,
+                        arguments,
+                        named,
+                    } => self.call(callee, token, arguments, named, OpCode::TailCall)?,
+                    _ => self.expression(value)?,
+                }
+                self.emit(OpCode::Return);
+            }
+            StmtKind::Import { keyword, .. } => {
+                return Err(ReefError::reef_error_at_line(
+                    keyword,
+                    "imports are not supported by the vm backend",
+                ));
+            }
+            StmtKind::Throw { keyword, .. } | StmtKind::Try { keyword, .. } => {
+                return Err(ReefError::reef_error_at_line(
+                    keyword,
+                    "exceptions are not supported by the vm backend",
+                ));
+            }
+            StmtKind::Error { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn emit_closure(&mut self, function: CompiledFunction) -> Result<(), ReefError> {
+        let index = self.make_constant(Value::Callable(Rc::new(function)))?;
+        self.emit_u16(OpCode::Closure, index);
+        Ok(())
+    }
+
+    fn function(
+        &mut self,
+        name: &Token,
+        parameters: &[Token],
+        defaults: &[ExprKind],
+        rest: Option<&Token>,
+        body: &[StmtKind],
+    ) -> Result<CompiledFunction, ReefError> {
+        if !defaults.is_empty() || rest.is_some() {
+            return Err(ReefError::reef_error_at_line(
+                name,
+                "default and rest parameters are not supported by the vm backend",
+            ));
+        }
+        // .reefc files store the arity in a byte
+        if parameters.len() > u8::MAX as usize {
+            return Err(ReefError::reef_error_at_line(
+                name,
+                "can't have more than 255 parameters",
+            ));
+        }
+        // compile the body with this compiler as its enclosing one, then
+        // take this one back whether or not that worked
+        let compiler = Compiler::new(&name.lexeme, parameters.len(), name.line);
+        let enclosing = mem::replace(self, compiler);
+        self.enclosing = Some(Box::new(enclosing));
+        let result = self.function_body(parameters, body);
+        let enclosing = self.enclosing.take().expect("set above");
+        let compiler = mem::replace(self, *enclosing);
+        result?;
+        Ok(compiler.finish())
+    }
+
+    fn function_body(&mut self, parameters: &[Token], body: &[StmtKind]) -> Result<(), ReefError> {
+        // parameters and the body share the function's scope
+        self.scope_depth = 1;
+        for param in parameters {
+            self.add_local(param)?;
+        }
+        for stmt in body {
+            self.statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        callee: &ExprKind,
+        token: &Token,
+        arguments: &[ExprKind],
+        named: &[(Token, ExprKind)],
+        op: OpCode,
+    ) -> Result<(), ReefError> {
+        if let Some((name, _)) = named.first() {
+            return Err(ReefError::reef_error_at_line(
+                name,
+                "named arguments are not supported by the vm backend",
+            ));
+        }
+        self.expression(callee)?;
+        for arg in arguments {
+            self.expression(arg)?;
+        }
+        let count = u8::try_from(arguments.len()).map_err(|_| {
+            ReefError::reef_error_at_line(token, "can't have more than 255 arguments")
+        })?;
+        self.line = token.line;
+        self.emit_byte(op, count);
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &ExprKind) -> Result<(), ReefError> {
+        match expr {
+            ExprKind::Literal { value, line } => {
+                self.line = *line;
+                match value {
+                    Literal::Nil => self.emit(OpCode::Nil),
+                    Literal::Boolean(true) => self.emit(OpCode::True),
+                    Literal::Boolean(false) => self.emit(OpCode::False),
+                    Literal::Number(n) => self.emit_constant(Value::Number(*n))?,
+                    Literal::String(s) => self.emit_constant(Value::String(s.clone()))?,
+                }
+            }
+            ExprKind::Grouping { expression } => self.expression(expression)?,
+            ExprKind::Unary { operator, right } => {
+                self.expression(right)?;
+                self.line = operator.line;
+                match operator.token_type {
+                    TokenType::Minus => self.emit(OpCode::Negate),
+                    _ => self.emit(OpCode::Not),
+                }
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.line = operator.line;
+                let op = match operator.token_type {
+                    TokenType::Plus => OpCode::Add,
+                    TokenType::Minus => OpCode::Subtract,
+                    TokenType::Star => OpCode::Multiply,
+                    TokenType::Slash => OpCode::Divide,
+                    TokenType::EqualEqual => OpCode::Equal,
+                    TokenType::BangEqual => OpCode::NotEqual,
+                    TokenType::Greater => OpCode::Greater,
+                    TokenType::GreaterEqual => OpCode::GreaterEqual,
+                    TokenType::Less => OpCode::Less,
+                    TokenType::LessEqual => OpCode::LessEqual,
+                    _ => {
+                        return Err(ReefError::reef_error_at_line(
+                            operator,
+                            "invalid binary operator",
+                        ));
+                    }
+                };
+                self.emit(op);
+            }
+            ExprKind::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                self.line = operator.line;
+                // the left operand stays on the stack as the result when
+                // it short circuits
+                let end_jump = if operator.token_type == TokenType::Or {
+                    let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                    let end_jump = self.emit_jump(OpCode::Jump);
+                    self.patch_jump(else_jump)?;
+                    end_jump
+                } else {
+                    self.emit_jump(OpCode::JumpIfFalse)
+                };
+                self.emit(OpCode::Pop);
+                self.expression(right)?;
+                self.patch_jump(end_jump)?;
+            }
+            ExprKind::Variable { name, .. } => {
+                self.line = name.line;
+                if let Some(slot) = self.resolve_local(name) {
+                    self.emit_byte(OpCode::GetLocal, slot);
+                } else if let Some(index) = self.resolve_upvalue(name)? {
+                    self.emit_byte(OpCode::GetUpvalue, index);
+                } else {
+                    let index = self.make_constant(Value::String(name.lexeme.clone()))?;
+                    self.emit_u16(OpCode::GetGlobal, index);
+                }
+            }
+            ExprKind::Assign { name, value, .. } => {
+                self.expression(value)?;
+                self.line = name.line;
+                if let Some(slot) = self.resolve_local(name) {
+                    self.emit_byte(OpCode::SetLocal, slot);
+                } else if let Some(index) = self.resolve_upvalue(name)? {
+                    self.emit_byte(OpCode::SetUpvalue, index);
+                } else {
+                    let index = self.make_constant(Value::String(name.lexeme.clone()))?;
+                    self.emit_u16(OpCode::SetGlobal, index);
+                }
+            }
+            ExprKind::Call {
+                callee,
+                token,
+                arguments,
+                named,
+            } => self.call(callee, token, arguments, named, OpCode::Call)?,
+            ExprKind::None => self.emit(OpCode::Nil),
+            ExprKind::Function {
+                name,
+                parameters,
+                defaults,
+                rest,
+                body,
+            } => {
+                let function = self.function(name, parameters, defaults, rest.as_ref(), body)?;
+                self.line = name.line;
+                self.emit_closure(function)?;
+            }
+            ExprKind::Get { object, name } => {
+                self.expression(object)?;
+                self.line = name.line;
+                let index = self.make_constant(Value::String(name.lexeme.clone()))?;
+                self.emit_u16(OpCode::GetProperty, index);
+            }
+            ExprKind::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.expression(object)?;
+                self.expression(value)?;
+                self.line = name.line;
+                let index = self.make_constant(Value::String(name.lexeme.clone()))?;
+                self.emit_u16(OpCode::SetProperty, index);
+            }
+            ExprKind::Super { .. } | ExprKind::This { .. } => {
+                return Err(ReefError::reef_error(
+                    self.line,
+                    "classes are not supported by the vm backend",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compiler;
+    use crate::stmt::StmtKind;
+    use crate::vm::chunk::OpCode;
+    use crate::{Parser, Scanner, Token, TokenType};
+
+    fn ops(source: &str) -> Vec<OpCode> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let function = Compiler::compile(&stmts).unwrap();
+        let code = &function.chunk.code;
+        let mut ops = Vec::new();
+        let mut offset = 0;
+        while offset < code.len() {
+            let op = OpCode::from_byte(code[offset]).unwrap();
+            ops.push(op);
+            offset += 1 + op.operand_len();
+        }
+        ops
+    }
+
+    #[test]
+    fn test_block_locals_use_slots() {
+        assert_eq!(
+            ops("{ var a = 1; print a; }"),
+            vec![
+                OpCode::Constant,
+                OpCode::GetLocal,
+                OpCode::Print,
+                OpCode::Pop,
+                OpCode::Nil,
+                OpCode::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_captured_locals_are_closed_over() {
+        // `get` is popped, `n` outlives its slot in get's upvalue
+        assert_eq!(
+            ops("{ var n = 1; fun get() { return n; } }"),
+            vec![
+                OpCode::Constant,
+                OpCode::Closure,
+                OpCode::Pop,
+                OpCode::CloseUpvalue,
+                OpCode::Nil,
+                OpCode::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_too_many_parameters_are_rejected() {
+        // the parser stops at 255, so build the declaration directly
+        let identifier = |name: String| Token::new(TokenType::Identifier, name, None, 1);
+        let function = StmtKind::Function {
+            name: identifier("f".to_string()),
+            parameters: (0..256).map(|i| identifier(format!("p{}", i))).collect(),
+            defaults: Vec::new(),
+            rest: None,
+            body: Vec::new(),
+        };
+        let error = Compiler::compile(&[function]).unwrap_err();
+        assert!(
+            error
+                .message()
+                .ends_with("can't have more than 255 parameters")
+        );
+    }
+
+    #[test]
+    fn test_top_level_return_is_rejected() {
+        let tokens = Scanner::new("return 1;".to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        assert!(Compiler::compile(&stmts).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use std::{any::Any, fmt::Write};
+
+use super::{CompiledFunction, OpCode};
+use crate::expr::Value;
+
+fn constant_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        Value::Number(n) => n.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Nil => "nil".to_string(),
+        Value::Callable(callable) => format!("<fn {}>", callable.name()),
+        Value::Object(object) => object.display(),
+        Value::Module(module) => format!("<module {}>", module.name),
+    }
+}
+
+/// human readable listing of a function followed by every function
+/// nested in its constants
+pub fn disassemble(function: &CompiledFunction) -> String {
+    let mut out = String::new();
+    disassemble_into(function, &mut out);
+    out
+}
+
+fn disassemble_into(function: &CompiledFunction, out: &mut String) {
+    let chunk = &function.chunk;
+    let _ = writeln!(out, "== {} (arity {}) ==", function.name, function.arity);
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let line = chunk.lines[offset];
+        let _ = write!(out, "{:04} ", offset);
+        if offset > 0 && chunk.lines[offset - 1] == line {
+            let _ = write!(out, "   | ");
+        } else {
+            let _ = write!(out, "{:4} ", line);
+        }
+        let Some(op) = OpCode::from_byte(chunk.code[offset]) else {
+            let _ = writeln!(out, "<unknown {}>", chunk.code[offset]);
+            offset += 1;
+            continue;
+        };
+        let next = offset + 1 + op.operand_len();
+        if next > chunk.code.len() {
+            let _ = writeln!(out, "{:?} <truncated>", op);
+            break;
+        }
+        let name = format!("{:?}", op);
+        match op {
+            OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty => {
+                let index = chunk.read_u16(offset + 1) as usize;
+                let value = chunk
+                    .constants
+                    .get(index)
+                    .map(constant_text)
+                    .unwrap_or_default();
+                let _ = writeln!(out, "{:<14} {:4} {}", name, index, value);
+            }
+            OpCode::Closure => {
+                let index = chunk.read_u16(offset + 1) as usize;
+                let constant = chunk.constants.get(index);
+                let value = constant.map(constant_text).unwrap_or_default();
+                let _ = writeln!(out, "{:<14} {:4} {}", name, index, value);
+                let inner = constant.and_then(|constant| match constant {
+                    Value::Callable(callable) => {
+                        let any: &dyn Any = callable.as_ref();
+                        any.downcast_ref::<CompiledFunction>()
+                    }
+                    _ => None,
+                });
+                for upvalue in inner.iter().flat_map(|inner| &inner.upvalues) {
+                    let kind = if upvalue.is_local { "local" } else { "upvalue" };
+                    let _ = writeln!(out, "{:04}    |   {} {}", offset, kind, upvalue.index);
+                }
+            }
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::Call
+            | OpCode::TailCall => {
+                let _ = writeln!(out, "{:<14} {:4}", name, chunk.code[offset + 1]);
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+                let jump = chunk.read_u16(offset + 1) as usize;
+                let target = if op == OpCode::Loop {
+                    next.wrapping_sub(jump)
+                } else {
+                    next + jump
+                };
+                let _ = writeln!(out, "{:<14} {:4} -> {:04}", name, jump, target);
+            }
+            _ => {
+                let _ = writeln!(out, "{}", name);
+            }
+        }
+        offset = next;
+    }
+
+    for constant in &chunk.constants {
+        if let Value::Callable(callable) = constant {
+            let any: &dyn Any = callable.as_ref();
+            if let Some(inner) = any.downcast_ref::<CompiledFunction>() {
+                out.push('\n');
+                disassemble_into(inner, out);
+            }
+        }
+    }
+}
@@ -0,0 +1,669 @@
+use std::{any::Any, cell::RefCell, collections::HashMap, io::Write, mem, rc::Rc};
+
+use super::{Captured, Closure, CompiledFunction, chunk::OpCode};
+use crate::{
+    error::ReefError,
+    expr::Value,
+    interpreter::{DEFAULT_MAX_CALL_DEPTH, Interpreter, elide_trace, is_equal},
+    output,
+    suggest::did_you_mean,
+};
+
+#[derive(Debug)]
+struct Frame {
+    function: Rc<CompiledFunction>,
+    upvalues: Rc<[Rc<RefCell<Captured>>]>,
+    ip: usize,
+    // stack index of slot 0
+    base: usize,
+}
+
+impl Frame {
+    fn read_byte(&mut self) -> u8 {
+        let byte = self.function.chunk.code[self.ip];
+        self.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let value = self.function.chunk.read_u16(self.ip);
+        self.ip += 2;
+        value
+    }
+
+    fn name(&self, index: u16) -> &str {
+        match &self.function.chunk.constants[index as usize] {
+            Value::String(name) => name,
+            _ => "",
+        }
+    }
+
+    fn line(&self) -> usize {
+        self.function.chunk.lines[self.ip.saturating_sub(1)]
+    }
+}
+
+/// stack machine running compiled reef
+pub struct Vm {
+    machine: Machine,
+    // natives are written against the tree-walker, this one backs their calls
+    host: Interpreter,
+}
+
+/// state of a running vm. the frame being executed is kept out of
+/// `frames`, which only holds its callers. natives borrow it through the
+/// host interpreter while they run, so they can call back into closures
+#[derive(Default)]
+pub(crate) struct Machine {
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    globals: HashMap<String, Value>,
+    // captured locals still on the stack, shared by every closure using
+    // the same slot
+    open_upvalues: Vec<Rc<RefCell<Captured>>>,
+    max_call_depth: usize,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let host = Interpreter::new();
+        let globals = host.globals.borrow().bindings().into_iter().collect();
+        Vm {
+            machine: Machine {
+                globals,
+                max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+                ..Machine::default()
+            },
+            host,
+        }
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.machine.globals.insert(name.to_string(), value);
+    }
+
+    /// frames aren't on the rust stack here, but scripts should fail at
+    /// the same depth on either backend
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.machine.max_call_depth = depth;
+        self.host.set_max_call_depth(depth);
+    }
+
+    /// sends `print` output to `sink`, returning the previous sink
+    pub fn set_output(&mut self, sink: Box<dyn Write>) -> Box<dyn Write> {
+        self.host.set_output(sink)
+    }
+
+    pub fn set_diagnostics(&mut self, sink: Box<dyn Write>) {
+        self.host.set_diagnostics(sink);
+    }
+
+    pub fn interpret(&mut self, script: Rc<CompiledFunction>) -> Result<(), ReefError> {
+        let machine = &mut self.machine;
+        machine.stack.push(Value::Callable(script.clone()));
+        let frame = Frame {
+            function: script,
+            upvalues: Rc::new([]),
+            ip: 0,
+            base: 0,
+        };
+        // error reports go wherever the host's would
+        let host = &mut self.host;
+        let sink = host.diagnostics.take();
+        let (result, sink) = output::with_diagnostics(sink, || machine.run(host, frame));
+        host.diagnostics = sink;
+        machine.stack.clear();
+        machine.frames.clear();
+        machine.open_upvalues.clear();
+        result.map(|_| ())
+    }
+}
+
+impl Machine {
+    // calls in progress, innermost first. a frame's ip sits just past the
+    // call it's waiting on
+    fn stack_trace(&self, current: &Frame) -> Vec<String> {
+        let callers = self.frames.iter().rev();
+        let callees = std::iter::once(current).chain(self.frames.iter().rev());
+        let lines = callees
+            .zip(callers)
+            .map(|(callee, caller)| {
+                format!(
+                    "in {}, called from line {}",
+                    callee.function.name,
+                    caller.line()
+                )
+            })
+            .collect();
+        elide_trace(lines)
+    }
+
+    // runs `call` with this machine lent to the host, where closures
+    // called back from a native find it
+    fn lend<T>(&mut self, host: &mut Interpreter, call: impl FnOnce(&mut Interpreter) -> T) -> T {
+        host.vm = Some(mem::take(self));
+        let result = call(host);
+        *self = host.vm.take().expect("closures give the vm back");
+        result
+    }
+
+    /// runs `closure` on top of whatever is running now, for a native
+    /// calling back into reef
+    pub(crate) fn call_closure(
+        &mut self,
+        host: &mut Interpreter,
+        closure: &Closure,
+        arguments: Vec<Value>,
+    ) -> Result<Value, ReefError> {
+        if self.frames.len() >= self.max_call_depth {
+            let line = self.frames.last().map_or(0, Frame::line);
+            return Err(ReefError::reef_line_runtime_error(line, "stack overflow"));
+        }
+        let depth = self.frames.len();
+        let base = self.stack.len();
+        // slot 0 would hold the callee, nothing reads it
+        self.stack.push(Value::Nil);
+        self.stack.extend(arguments);
+        let frame = Frame {
+            function: Rc::clone(&closure.function),
+            upvalues: Rc::clone(&closure.upvalues),
+            ip: 0,
+            base,
+        };
+        let result = self.run(host, frame);
+        if result.is_err() {
+            // the native may carry on after the error
+            self.frames.truncate(depth);
+            self.close_upvalues(base);
+            self.stack.truncate(base);
+        }
+        result
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap_or(Value::Nil)
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().unwrap_or(&Value::Nil)
+    }
+
+    fn pop_numbers(&mut self, frame: &Frame) -> Result<(f64, f64), ReefError> {
+        let right = self.pop();
+        let left = self.pop();
+        match (left, right) {
+            (Value::Number(l), Value::Number(r)) => Ok((l, r)),
+            _ => Err(runtime_error(frame, "Binary evaluation error")),
+        }
+    }
+
+    fn capture(&mut self, slot: usize) -> Rc<RefCell<Captured>> {
+        let open = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Captured::Open(s) if s == slot));
+        if let Some(upvalue) = open {
+            return Rc::clone(upvalue);
+        }
+        let upvalue = Rc::new(RefCell::new(Captured::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    // the stack is about to lose every slot from `from` up
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Captured::Open(slot) if slot >= from => {
+                    *upvalue = Captured::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn undefined_variable(&self, frame: &Frame, name: &str) -> ReefError {
+        let message = match did_you_mean(name, self.globals.keys().map(String::as_str)) {
+            Some(suggestion) => format!(
+                "undefined variable: '{}', did you mean '{}'?",
+                name, suggestion
+            ),
+            None => format!("undefined variable: '{}'", name),
+        };
+        runtime_error(frame, &message)
+    }
+
+    // runs until `frame` returns, giving back its result
+    fn run(&mut self, host: &mut Interpreter, mut frame: Frame) -> Result<Value, ReefError> {
+        let entry = self.frames.len();
+        loop {
+            let byte = frame.read_byte();
+            let Some(op) = OpCode::from_byte(byte) else {
+                return Err(runtime_error(&frame, &format!("invalid opcode {}", byte)));
+            };
+            match op {
+                OpCode::Constant => {
+                    let index = frame.read_u16() as usize;
+                    self.stack
+                        .push(frame.function.chunk.constants[index].clone());
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Boolean(true)),
+                OpCode::False => self.stack.push(Value::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = frame.base + frame.read_byte() as usize;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = frame.base + frame.read_byte() as usize;
+                    self.stack[slot] = self.peek().clone();
+                }
+                OpCode::GetUpvalue => {
+                    let index = frame.read_byte() as usize;
+                    let value = match &*frame.upvalues[index].borrow() {
+                        Captured::Open(slot) => self.stack[*slot].clone(),
+                        Captured::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = frame.read_byte() as usize;
+                    let value = self.peek().clone();
+                    match &mut *frame.upvalues[index].borrow_mut() {
+                        Captured::Open(slot) => self.stack[*slot] = value,
+                        Captured::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Closure => {
+                    let index = frame.read_u16() as usize;
+                    let constant = &frame.function.chunk.constants[index];
+                    let Some(function) = compiled(constant) else {
+                        return Err(runtime_error(&frame, "closure of a non-function"));
+                    };
+                    let upvalues = function
+                        .upvalues
+                        .iter()
+                        .map(|upvalue| match upvalue.is_local {
+                            true => self.capture(frame.base + upvalue.index as usize),
+                            false => Rc::clone(&frame.upvalues[upvalue.index as usize]),
+                        })
+                        .collect();
+                    let closure = Closure { function, upvalues };
+                    self.stack.push(Value::Callable(Rc::new(closure)));
+                }
+                OpCode::GetGlobal => {
+                    let index = frame.read_u16();
+                    let name = frame.name(index);
+                    match self.globals.get(name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.undefined_variable(&frame, name)),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let value = self.pop();
+                    let index = frame.read_u16();
+                    let name = frame.name(index);
+                    self.globals.insert(name.to_string(), value);
+                }
+                OpCode::SetGlobal => {
+                    let value = self.peek().clone();
+                    let index = frame.read_u16();
+                    let name = frame.name(index);
+                    match self.globals.get_mut(name) {
+                        Some(slot) => *slot = value,
+                        None => return Err(self.undefined_variable(&frame, name)),
+                    }
+                }
+                OpCode::GetProperty => {
+                    let object = self.pop();
+                    let index = frame.read_u16();
+                    let name = frame.name(index);
+                    let value = match self.lend(host, |host| host.get_property(object, name)) {
+                        Ok(value) => value?,
+                        Err(message) => return Err(runtime_error(&frame, &message)),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty => {
+                    let value = self.pop();
+                    let object = self.pop();
+                    let index = frame.read_u16();
+                    let name = frame.name(index);
+                    let value = match self.lend(host, |host| host.set_property(object, name, value))
+                    {
+                        Ok(value) => value?,
+                        Err(message) => return Err(runtime_error(&frame, &message)),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::Equal | OpCode::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let equal = is_equal(&left, &right);
+                    self.stack
+                        .push(Value::Boolean(equal == (op == OpCode::Equal)));
+                }
+                OpCode::Greater => {
+                    let (l, r) = self.pop_numbers(&frame)?;
+                    self.stack.push(Value::Boolean(l > r));
+                }
+                OpCode::GreaterEqual => {
+                    let (l, r) = self.pop_numbers(&frame)?;
+                    self.stack.push(Value::Boolean(l >= r));
+                }
+                OpCode::Less => {
+                    let (l, r) = self.pop_numbers(&frame)?;
+                    self.stack.push(Value::Boolean(l < r));
+                }
+                OpCode::LessEqual => {
+                    let (l, r) = self.pop_numbers(&frame)?;
+                    self.stack.push(Value::Boolean(l <= r));
+                }
+                OpCode::Add => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = match (left, right) {
+                        (Value::Number(l), Value::Number(r)) => Value::Number(l + r),
+                        (Value::String(l), Value::String(r)) => {
+                            Value::String(format!("{}{}", l, r).into())
+                        }
+                        _ => return Err(runtime_error(&frame, "Binary evaluation error")),
+                    };
+                    self.stack.push(result);
+                }
+                OpCode::Subtract => {
+                    let (l, r) = self.pop_numbers(&frame)?;
+                    self.stack.push(Value::Number(l - r));
+                }
+                OpCode::Multiply => {
+                    let (l, r) = self.pop_numbers(&frame)?;
+                    self.stack.push(Value::Number(l * r));
+                }
+                OpCode::Divide => {
+                    let (l, r) = self.pop_numbers(&frame)?;
+                    self.stack.push(Value::Number(l / r));
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Boolean(!value.is_truthy()));
+                }
+                OpCode::Negate => match self.pop() {
+                    Value::Number(n) => self.stack.push(Value::Number(-n)),
+                    _ => return Err(runtime_error(&frame, "Operand must be a number")),
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    let text = host.stringify(&value);
+                    host.write_output(&text);
+                }
+                OpCode::Jump => {
+                    let offset = frame.read_u16() as usize;
+                    frame.ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = frame.read_u16() as usize;
+                    if !self.peek().is_truthy() {
+                        frame.ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = frame.read_u16() as usize;
+                    frame.ip -= offset;
+                }
+                OpCode::Call => {
+                    let count = frame.read_byte() as usize;
+                    frame = self.call_value(host, frame, count)?;
+                }
+                OpCode::TailCall => {
+                    let count = frame.read_byte() as usize;
+                    frame = self.tail_call(host, frame, count)?;
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.len() == entry {
+                        return Ok(result);
+                    }
+                    frame = self.frames.pop().expect("callers are above the entry");
+                    self.stack.push(result);
+                }
+            }
+        }
+    }
+
+    // returns the frame to continue in: the callee's for compiled
+    // functions, the caller's after a native call
+    fn call_value(
+        &mut self,
+        host: &mut Interpreter,
+        frame: Frame,
+        count: usize,
+    ) -> Result<Frame, ReefError> {
+        let base = self.stack.len() - count - 1;
+        let Value::Callable(callable) = &self.stack[base] else {
+            return Err(runtime_error(&frame, "can only call funcs and classes"));
+        };
+        if !callable.arity_spec().accepts(count) {
+            let message = format!(
+                "Expected: {} args, got {} args",
+                callable.arity_spec(),
+                count
+            );
+            return Err(runtime_error(&frame, &message));
+        }
+        let callable = Rc::clone(callable);
+        let any: Rc<dyn Any> = callable.clone();
+        match any.downcast::<Closure>() {
+            Ok(closure) => {
+                if self.frames.len() >= self.max_call_depth {
+                    let trace = self.stack_trace(&frame).join("\n  ");
+                    let message = format!("stack overflow\n  {}", trace);
+                    return Err(runtime_error(&frame, &message));
+                }
+                self.frames.push(frame);
+                Ok(Frame {
+                    function: Rc::clone(&closure.function),
+                    upvalues: Rc::clone(&closure.upvalues),
+                    ip: 0,
+                    base,
+                })
+            }
+            Err(_) => {
+                let arguments = self.stack.split_off(base + 1);
+                self.stack.pop();
+                // on the stack for traces while the native runs
+                self.frames.push(frame);
+                let result = self.lend(host, |host| callable.call(host, arguments));
+                let frame = self.frames.pop().expect("pushed above");
+                self.stack.push(result?);
+                Ok(frame)
+            }
+        }
+    }
+
+    // like a call, but a compiled callee takes over the caller's frame and
+    // stack window instead of going on top of them
+    fn tail_call(
+        &mut self,
+        host: &mut Interpreter,
+        frame: Frame,
+        count: usize,
+    ) -> Result<Frame, ReefError> {
+        let depth = self.frames.len();
+        let callee = self.call_value(host, frame, count)?;
+        if self.frames.len() == depth {
+            return Ok(callee);
+        }
+        let caller = self.frames.pop().expect("call_value pushed the caller");
+        self.close_upvalues(caller.base);
+        self.stack.drain(caller.base..callee.base);
+        Ok(Frame {
+            base: caller.base,
+            ..callee
+        })
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+fn compiled(value: &Value) -> Option<Rc<CompiledFunction>> {
+    match value {
+        Value::Callable(callable) => {
+            let any: Rc<dyn Any> = Rc::clone(callable) as Rc<dyn Any>;
+            any.downcast().ok()
+        }
+        _ => None,
+    }
+}
+
+fn runtime_error(frame: &Frame, message: &str) -> ReefError {
+    ReefError::reef_line_runtime_error(frame.line(), message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::Vm;
+    use crate::expr::Value;
+    use crate::func::{Arity, HostFunction};
+    use crate::object::HostType;
+    use crate::output::Capture;
+    use crate::vm::Compiler;
+    use crate::{Parser, Scanner, error::ReefError};
+
+    // what the script printed
+    fn run(source: &str) -> Result<String, ReefError> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse()?;
+        let mut vm = Vm::new();
+        let output = Capture::new();
+        vm.set_output(Box::new(output.clone()));
+        vm.interpret(Compiler::compile(&stmts)?)?;
+        Ok(output.take())
+    }
+
+    #[test]
+    fn test_recursion_and_natives() {
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\nprint fib(15);\nprint clock() > 0;";
+        assert_eq!(run(source).unwrap(), "610\ntrue\n");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        assert!(run("var a = 1; a = missing;").is_err());
+        assert!(run("print -\"a\";").is_err());
+        assert!(run("fun f(a) {} f();").is_err());
+        assert!(run("\"a\"();").is_err());
+    }
+
+    #[test]
+    fn test_tail_calls_reuse_the_frame() {
+        let source =
+            "fun count(n) { if (n == 0) return 0; return count(n - 1); }\nprint count(1000000);";
+        assert_eq!(run(source).unwrap(), "0\n");
+        // the same call without `return` still overflows
+        assert!(
+            run("fun count(n) { if (n == 0) return 0; count(n - 1); }\ncount(1000000);").is_err()
+        );
+    }
+
+    #[test]
+    fn test_host_natives() {
+        let mut vm = Vm::new();
+        let native = HostFunction {
+            name: "count".into(),
+            arity: Arity::AtLeast(1),
+            func: Box::new(|_, args| Ok(Value::Number(args.len() as f64))),
+        };
+        vm.define_global("count", Value::Callable(Rc::new(native)));
+        let output = Capture::new();
+        vm.set_output(Box::new(output.clone()));
+        let tokens = Scanner::new("print count(1, 2, 3);".to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        assert!(vm.interpret(Compiler::compile(&stmts).unwrap()).is_ok());
+        assert_eq!(output.take(), "3\n");
+        let stmts = Parser::new(Scanner::new("count();".to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        assert!(vm.interpret(Compiler::compile(&stmts).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_natives_call_back_into_closures() {
+        let mut vm = Vm::new();
+        let apply = HostFunction {
+            name: "apply".into(),
+            arity: Arity::Exactly(2),
+            func: Box::new(|interpreter, args| interpreter.call(&args[0], vec![args[1].clone()])),
+        };
+        vm.define_global("apply", Value::Callable(Rc::new(apply)));
+        let output = Capture::new();
+        vm.set_output(Box::new(output.clone()));
+        let source = "fun inc(n) { return n + 1; }\nprint apply(inc, 1);\n\
+            fun adder(a) { fun add(b) { return a + b; } return add; }\n\
+            var k = 10; print apply(adder(k), apply(inc, 4));\n\
+            print apply(fun (n) { return apply(inc, n) * 2; }, 3);";
+        let stmts = Parser::new(Scanner::new(source.to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        assert!(vm.interpret(Compiler::compile(&stmts).unwrap()).is_ok());
+        assert_eq!(output.take(), "2\n15\n8\n");
+
+        // an error in the callback comes back through the native
+        let source = "print apply(fun (n) { return -n; }, \"a\");";
+        let stmts = Parser::new(Scanner::new(source.to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        assert!(vm.interpret(Compiler::compile(&stmts).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_host_object_properties() {
+        let mut counter = HostType::new("Counter");
+        counter
+            .method("bump", 0, |_, this, _| {
+                let count = this.downcast_ref::<Cell<f64>>().unwrap();
+                count.set(count.get() + 1.0);
+                Ok(Value::Number(count.get()))
+            })
+            .property("count", |_, this| {
+                Ok(Value::Number(
+                    this.downcast_ref::<Cell<f64>>().unwrap().get(),
+                ))
+            });
+        let counter = Rc::new(counter);
+
+        let mut vm = Vm::new();
+        vm.define_global("c", counter.instance(Cell::new(0.0)));
+        let output = Capture::new();
+        vm.set_output(Box::new(output.clone()));
+        let source = "c.bump(); print c.bump(); print c.count; print c;";
+        let stmts = Parser::new(Scanner::new(source.to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        assert!(vm.interpret(Compiler::compile(&stmts).unwrap()).is_ok());
+        assert_eq!(output.take(), "2\n2\n<Counter object>\n");
+
+        let stmts = Parser::new(Scanner::new("c.count = 3;".to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        assert!(vm.interpret(Compiler::compile(&stmts).unwrap()).is_err());
+    }
+}
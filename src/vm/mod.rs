@@ -0,0 +1,92 @@
+pub mod chunk;
+pub mod compiler;
+pub mod disasm;
+pub mod machine;
+pub mod serialize;
+pub mod verify;
+
+pub use chunk::{Chunk, OpCode};
+pub use compiler::Compiler;
+pub use machine::Vm;
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{error::ReefError, expr::Value, func::ReefCallable, interpreter::Interpreter};
+
+/// a function body compiled to bytecode. the top level script is one
+/// too, with arity 0
+#[derive(Debug)]
+pub struct CompiledFunction {
+    pub name: String,
+    pub arity: usize,
+    pub chunk: Chunk,
+    /// variables of enclosing functions it uses, captured when the
+    /// `Closure` instruction creating it runs
+    pub upvalues: Vec<Upvalue>,
+}
+
+/// where a closure finds a variable it captures, relative to the
+/// function creating it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Upvalue {
+    // a local slot of that function, or one of its own upvalues
+    pub is_local: bool,
+    pub index: u8,
+}
+
+/// a captured variable. open while it still lives in a stack slot,
+/// closed over its value once that slot is gone
+#[derive(Debug)]
+pub enum Captured {
+    Open(usize),
+    Closed(Value),
+}
+
+/// a compiled function with the variables it captured, what scripts
+/// running on the vm call
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub upvalues: Rc<[Rc<RefCell<Captured>>]>,
+}
+
+impl ReefCallable for CompiledFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _arguments: Vec<Value>,
+    ) -> Result<Value, ReefError> {
+        Err(ReefError::reef_general_error(&format!(
+            "compiled function '{}' can only run on the vm",
+            self.name
+        )))
+    }
+}
+
+impl ReefCallable for Closure {
+    fn arity(&self) -> usize {
+        self.function.arity
+    }
+    fn name(&self) -> &str {
+        &self.function.name
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, ReefError> {
+        // a native calling back while the vm runs has it lent to them
+        let Some(mut machine) = interpreter.vm.take() else {
+            return self.function.call(interpreter, arguments);
+        };
+        let result = machine.call_closure(interpreter, self, arguments);
+        interpreter.vm = Some(machine);
+        result
+    }
+}
@@ -0,0 +1,331 @@
+use std::{any::Any, rc::Rc};
+
+use super::{Chunk, CompiledFunction, Upvalue, verify::verify};
+use crate::{error::ReefError, expr::Value};
+
+/// layout of a `.reefc` file, all integers little endian:
+///
+///   magic "REEF", version u16, function count u32, then per function:
+///   name (u32 length + utf-8), arity u8,
+///   upvalues (u8 count, each is_local u8 then index u8),
+///   constants (u32 count, each a tag byte then f64 | string | u32 function index),
+///   code (u32 length + bytes),
+///   line table (u32 count of (line u32, run u32) pairs covering the code)
+///
+/// function 0 is the script. a function constant always points at a later
+/// function, so the table can't describe cycles
+pub const MAGIC: &[u8; 4] = b"REEF";
+pub const VERSION: u16 = 2;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn as_compiled(value: &Value) -> Option<&CompiledFunction> {
+    match value {
+        Value::Callable(callable) => {
+            let any: &dyn Any = callable.as_ref();
+            any.downcast_ref::<CompiledFunction>()
+        }
+        _ => None,
+    }
+}
+
+// pre-order, so every function lands before the ones it contains
+fn collect<'a>(function: &'a CompiledFunction, functions: &mut Vec<&'a CompiledFunction>) {
+    functions.push(function);
+    for constant in &function.chunk.constants {
+        if let Some(child) = as_compiled(constant) {
+            collect(child, functions);
+        }
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, text: &str) {
+    write_u32(out, text.len());
+    out.extend_from_slice(text.as_bytes());
+}
+
+pub fn encode(script: &CompiledFunction) -> Vec<u8> {
+    let mut functions = Vec::new();
+    collect(script, &mut functions);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    write_u32(&mut out, functions.len());
+    for function in &functions {
+        write_str(&mut out, &function.name);
+        out.push(u8::try_from(function.arity).expect("the compiler caps arity at 255"));
+        out.push(u8::try_from(function.upvalues.len()).expect("the compiler caps upvalues at 255"));
+        for upvalue in &function.upvalues {
+            out.push(upvalue.is_local as u8);
+            out.push(upvalue.index);
+        }
+
+        let chunk = &function.chunk;
+        write_u32(&mut out, chunk.constants.len());
+        for constant in &chunk.constants {
+            match constant {
+                Value::Number(n) => {
+                    out.push(TAG_NUMBER);
+                    out.extend_from_slice(&n.to_le_bytes());
+                }
+                Value::String(s) => {
+                    out.push(TAG_STRING);
+                    write_str(&mut out, s);
+                }
+                _ => {
+                    // the compiler only emits numbers, strings and functions
+                    let index = as_compiled(constant)
+                        .and_then(|child| functions.iter().position(|f| std::ptr::eq(*f, child)))
+                        .expect("unsupported constant in compiled chunk");
+                    out.push(TAG_FUNCTION);
+                    write_u32(&mut out, index);
+                }
+            }
+        }
+
+        write_u32(&mut out, chunk.code.len());
+        out.extend_from_slice(&chunk.code);
+
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for &line in &chunk.lines {
+            match runs.last_mut() {
+                Some((last, count)) if *last == line => *count += 1,
+                _ => runs.push((line, 1)),
+            }
+        }
+        write_u32(&mut out, runs.len());
+        for (line, count) in runs {
+            write_u32(&mut out, line);
+            write_u32(&mut out, count);
+        }
+    }
+    out
+}
+
+enum RawConstant {
+    Value(Value),
+    Function(usize),
+}
+
+struct RawFunction {
+    name: String,
+    arity: usize,
+    upvalues: Vec<Upvalue>,
+    constants: Vec<RawConstant>,
+    code: Vec<u8>,
+    lines: Vec<usize>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ReefError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid("unexpected end of file"))?;
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ReefError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ReefError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<usize, ReefError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn f64(&mut self) -> Result<f64, ReefError> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, ReefError> {
+        let len = self.u32()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("string is not valid utf-8"))
+    }
+
+    fn function(&mut self) -> Result<RawFunction, ReefError> {
+        let name = self.string()?;
+        let arity = self.u8()? as usize;
+
+        let mut upvalues = Vec::new();
+        for _ in 0..self.u8()? {
+            let is_local = match self.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(invalid("upvalue kind must be 0 or 1")),
+            };
+            let index = self.u8()?;
+            upvalues.push(Upvalue { is_local, index });
+        }
+
+        // counts come from the file, so never preallocate from them
+        let mut constants = Vec::new();
+        for _ in 0..self.u32()? {
+            let constant = match self.u8()? {
+                TAG_NUMBER => RawConstant::Value(Value::Number(self.f64()?)),
+                TAG_STRING => RawConstant::Value(Value::String(self.string()?.into())),
+                TAG_FUNCTION => RawConstant::Function(self.u32()?),
+                tag => return Err(invalid(&format!("unknown constant tag {}", tag))),
+            };
+            constants.push(constant);
+        }
+
+        let code_len = self.u32()?;
+        let code = self.take(code_len)?.to_vec();
+
+        let mut lines = Vec::new();
+        for _ in 0..self.u32()? {
+            let line = self.u32()?;
+            let count = self.u32()?;
+            if lines.len() + count > code.len() {
+                return Err(invalid("line table is longer than the code"));
+            }
+            lines.extend(std::iter::repeat_n(line, count));
+        }
+        if lines.len() != code.len() {
+            return Err(invalid("line table doesn't cover the code"));
+        }
+
+        Ok(RawFunction {
+            name,
+            arity,
+            upvalues,
+            constants,
+            code,
+            lines,
+        })
+    }
+}
+
+fn invalid(message: &str) -> ReefError {
+    ReefError::reef_general_error(&format!("invalid bytecode file: {}", message))
+}
+
+/// loads and verifies a `.reefc` file, returning the script function
+pub fn decode(bytes: &[u8]) -> Result<Rc<CompiledFunction>, ReefError> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("bad magic number"));
+    }
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(invalid(&format!(
+            "unsupported version {} (expected {})",
+            version, VERSION
+        )));
+    }
+    let count = reader.u32()?;
+    let mut raw = Vec::new();
+    for _ in 0..count {
+        raw.push(reader.function()?);
+    }
+    if raw.is_empty() {
+        return Err(invalid("no script function"));
+    }
+    if reader.offset != bytes.len() {
+        return Err(invalid("trailing bytes after the last function"));
+    }
+
+    // build from the back so children exist before their parents
+    let mut built: Vec<Option<Rc<CompiledFunction>>> = vec![None; raw.len()];
+    for (index, function) in raw.into_iter().enumerate().rev() {
+        let mut constants = Vec::new();
+        for constant in function.constants {
+            constants.push(match constant {
+                RawConstant::Value(value) => value,
+                RawConstant::Function(child) if child > index => {
+                    let child = built
+                        .get_mut(child)
+                        .and_then(Option::take)
+                        .ok_or_else(|| invalid("function referenced twice or out of range"))?;
+                    Value::Callable(child)
+                }
+                RawConstant::Function(_) => {
+                    return Err(invalid("function constant must point forward"));
+                }
+            });
+        }
+        built[index] = Some(Rc::new(CompiledFunction {
+            name: function.name,
+            arity: function.arity,
+            chunk: Chunk {
+                code: function.code,
+                constants,
+                lines: function.lines,
+            },
+            upvalues: function.upvalues,
+        }));
+    }
+    let script = built[0]
+        .take()
+        .ok_or_else(|| invalid("no script function"))?;
+    if !script.upvalues.is_empty() {
+        return Err(invalid("the script can't capture variables"));
+    }
+    verify(&script)?;
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+    use crate::vm::Compiler;
+    use crate::vm::disasm::disassemble;
+    use crate::{Parser, Scanner};
+
+    fn compile(source: &str) -> Vec<u8> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        encode(&Compiler::compile(&stmts).unwrap())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = "fun add(a, b) { fun twice(x) { return x + x; } return twice(a) + b; }\nprint add(1, \"s\");";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let script = Compiler::compile(&stmts).unwrap();
+        let loaded = decode(&encode(&script)).unwrap();
+        assert_eq!(disassemble(&script), disassemble(&loaded));
+    }
+
+    #[test]
+    fn test_rejects_malformed_files() {
+        let bytes = compile("var a = 1; { var b = a; print b; }");
+        assert!(decode(&bytes).is_ok());
+        assert!(decode(b"nope").is_err());
+        // every truncation must be caught, never panic
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err());
+        }
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 99;
+        assert!(decode(&wrong_version).is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(decode(&trailing).is_err());
+    }
+}
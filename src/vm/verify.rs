@@ -0,0 +1,205 @@
+use std::any::Any;
+
+use super::{CompiledFunction, OpCode};
+use crate::{error::ReefError, expr::Value};
+
+fn invalid(function: &CompiledFunction, offset: usize, message: &str) -> ReefError {
+    ReefError::reef_general_error(&format!(
+        "invalid bytecode in '{}' at {:04}: {}",
+        function.name, offset, message
+    ))
+}
+
+/// checks a function and everything it contains is safe to run: valid
+/// opcodes and operands, jumps that land inside the code, no stack
+/// underflow, local slots and upvalues that exist and the same stack
+/// height on every path into an instruction
+pub fn verify(function: &CompiledFunction) -> Result<(), ReefError> {
+    let chunk = &function.chunk;
+    let code = &chunk.code;
+    if chunk.lines.len() != code.len() {
+        return Err(invalid(function, 0, "line table doesn't match the code"));
+    }
+
+    // stack height relative to the frame base, slot 0 and the arguments
+    // are there on entry
+    let mut heights: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending = vec![(0, function.arity + 1)];
+    while let Some((offset, height)) = pending.pop() {
+        if offset >= code.len() {
+            return Err(invalid(function, offset, "runs past the end of the code"));
+        }
+        match heights[offset] {
+            Some(seen) if seen == height => continue,
+            Some(_) => return Err(invalid(function, offset, "inconsistent stack height")),
+            None => heights[offset] = Some(height),
+        }
+        let Some(op) = OpCode::from_byte(code[offset]) else {
+            return Err(invalid(function, offset, "unknown opcode"));
+        };
+        let next = offset + 1 + op.operand_len();
+        if next > code.len() {
+            return Err(invalid(function, offset, "truncated operand"));
+        }
+        let operand = match op.operand_len() {
+            1 => code[offset + 1] as usize,
+            2 => chunk.read_u16(offset + 1) as usize,
+            _ => 0,
+        };
+
+        let (pops, pushes) = match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetLocal
+            | OpCode::GetUpvalue
+            | OpCode::GetGlobal
+            | OpCode::Closure => (0, 1),
+            OpCode::Pop | OpCode::CloseUpvalue | OpCode::DefineGlobal | OpCode::Print => (1, 0),
+            OpCode::SetLocal
+            | OpCode::SetUpvalue
+            | OpCode::SetGlobal
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::GetProperty
+            | OpCode::JumpIfFalse => (1, 1),
+            OpCode::Equal
+            | OpCode::NotEqual
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::SetProperty => (2, 1),
+            OpCode::Call | OpCode::TailCall => (operand + 1, 1),
+            OpCode::Return => (1, 0),
+            OpCode::Jump | OpCode::Loop => (0, 0),
+        };
+        if height < pops {
+            return Err(invalid(function, offset, "stack underflow"));
+        }
+        let after = height - pops + pushes;
+
+        match op {
+            OpCode::Constant => match chunk.constants.get(operand) {
+                None => return Err(invalid(function, offset, "constant out of range")),
+                // functions only run as closures
+                Some(Value::Callable(_)) => {
+                    return Err(invalid(
+                        function,
+                        offset,
+                        "function constant outside a closure",
+                    ));
+                }
+                Some(_) => {}
+            },
+            OpCode::Closure => {
+                let inner = match chunk.constants.get(operand) {
+                    Some(Value::Callable(callable)) => {
+                        let any: &dyn Any = callable.as_ref();
+                        any.downcast_ref::<CompiledFunction>()
+                    }
+                    _ => None,
+                };
+                let Some(inner) = inner else {
+                    return Err(invalid(function, offset, "closure of a non-function"));
+                };
+                // a local function captures the slot it's about to land in
+                let captures_ok = inner.upvalues.iter().all(|upvalue| match upvalue.is_local {
+                    true => (upvalue.index as usize) <= height,
+                    false => (upvalue.index as usize) < function.upvalues.len(),
+                });
+                if !captures_ok {
+                    return Err(invalid(function, offset, "captured variable out of range"));
+                }
+                verify(inner)?;
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue if operand >= function.upvalues.len() => {
+                return Err(invalid(function, offset, "upvalue out of range"));
+            }
+            OpCode::GetGlobal | OpCode::DefineGlobal | OpCode::SetGlobal
+                if !matches!(chunk.constants.get(operand), Some(Value::String(_))) =>
+            {
+                return Err(invalid(function, offset, "global name must be a string"));
+            }
+            OpCode::GetProperty | OpCode::SetProperty
+                if !matches!(chunk.constants.get(operand), Some(Value::String(_))) =>
+            {
+                return Err(invalid(function, offset, "property name must be a string"));
+            }
+            OpCode::GetLocal | OpCode::SetLocal if operand >= height => {
+                return Err(invalid(function, offset, "local slot out of range"));
+            }
+            _ => {}
+        }
+
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump => pending.push((next + operand, after)),
+            OpCode::Loop => match next.checked_sub(operand) {
+                Some(target) => pending.push((target, after)),
+                None => return Err(invalid(function, offset, "loop jumps before the code")),
+            },
+            OpCode::JumpIfFalse => {
+                pending.push((next + operand, after));
+                pending.push((next, after));
+            }
+            _ => pending.push((next, after)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify;
+    use crate::vm::{Chunk, CompiledFunction, Compiler, OpCode};
+    use crate::{Parser, Scanner};
+
+    fn function(code: Vec<u8>) -> CompiledFunction {
+        let lines = vec![1; code.len()];
+        CompiledFunction {
+            name: "script".to_string(),
+            arity: 0,
+            chunk: Chunk {
+                code,
+                constants: Vec::new(),
+                lines,
+            },
+            upvalues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_compiler_output_verifies() {
+        let source = include_str!("../../reef/tests/functions.reef");
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        assert!(verify(&Compiler::compile(&stmts).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_unsafe_code() {
+        let ret = OpCode::Return as u8;
+        let nil = OpCode::Nil as u8;
+        assert!(verify(&function(vec![nil, ret])).is_ok());
+        // pops more than the frame holds, slot 0 included
+        let pop = OpCode::Pop as u8;
+        assert!(verify(&function(vec![pop, pop, nil, ret])).is_err());
+        // local slot past the stack
+        assert!(verify(&function(vec![OpCode::GetLocal as u8, 3, ret])).is_err());
+        // falls off the end
+        assert!(verify(&function(vec![nil])).is_err());
+        // constant that doesn't exist
+        assert!(verify(&function(vec![OpCode::Constant as u8, 0, 0, ret])).is_err());
+        // jump out of the code
+        assert!(verify(&function(vec![OpCode::Jump as u8, 0, 9, nil, ret])).is_err());
+        // upvalue the function doesn't have
+        assert!(verify(&function(vec![OpCode::GetUpvalue as u8, 0, ret])).is_err());
+        assert!(verify(&function(vec![0xff])).is_err());
+    }
+}
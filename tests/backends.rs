@@ -0,0 +1,234 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(script: &Path, backend: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_reef"))
+        .args(["run", script.to_str().unwrap(), "--backend", backend])
+        .output()
+        .expect("failed to run reef")
+}
+
+fn corpus() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("reef");
+    let mut scripts = vec![root.join("hello.reef")];
+    for entry in fs::read_dir(root.join("tests")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "reef") {
+            scripts.push(path);
+        }
+    }
+    scripts.sort();
+    scripts
+}
+
+#[test]
+fn vm_matches_tree_walker_on_corpus() {
+    for script in corpus() {
+        let tree = run(&script, "tree");
+        let vm = run(&script, "vm");
+        assert!(tree.status.success(), "{} failed on tree", script.display());
+        assert_eq!(
+            String::from_utf8_lossy(&tree.stdout),
+            String::from_utf8_lossy(&vm.stdout),
+            "{} differs between backends",
+            script.display()
+        );
+        assert_eq!(tree.status.code(), vm.status.code());
+    }
+}
+
+#[test]
+fn runtime_errors_exit_70_on_both_backends() {
+    let path = std::env::temp_dir().join(format!("reef_backend_{}.reef", std::process::id()));
+    fs::write(
+        &path,
+        "print \"before\";\nprint -\"oops\";\nprint \"after\";\n",
+    )
+    .unwrap();
+    for backend in ["tree", "vm"] {
+        let output = run(&path, backend);
+        assert_eq!(output.status.code(), Some(70), "{}", backend);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn compiled_bytecode_matches_source() {
+    for script in corpus() {
+        let compiled = std::env::temp_dir().join(format!(
+            "reef_{}_{}.reefc",
+            std::process::id(),
+            script.file_stem().unwrap().to_string_lossy()
+        ));
+        let status = Command::new(env!("CARGO_BIN_EXE_reef"))
+            .args(["compile", script.to_str().unwrap(), "-o"])
+            .arg(&compiled)
+            .status()
+            .unwrap();
+        assert!(status.success(), "{} failed to compile", script.display());
+        assert_eq!(
+            String::from_utf8_lossy(&run(&script, "vm").stdout),
+            String::from_utf8_lossy(&run(&compiled, "vm").stdout),
+            "{} differs once compiled",
+            script.display()
+        );
+        fs::remove_file(compiled).unwrap();
+    }
+}
+
+#[test]
+fn gc_stress_does_not_change_output() {
+    for script in corpus() {
+        let normal = run(&script, "tree");
+        let stressed = Command::new(env!("CARGO_BIN_EXE_reef"))
+            .args(["run", script.to_str().unwrap(), "--gc-stress", "--gc-stats"])
+            .output()
+            .expect("failed to run reef");
+        assert_eq!(normal.stdout, stressed.stdout, "{}", script.display());
+        assert_eq!(normal.status.code(), stressed.status.code());
+        assert!(String::from_utf8_lossy(&stressed.stderr).starts_with("gc: "));
+    }
+}
+
+#[test]
+fn optimizer_does_not_change_output() {
+    for script in corpus() {
+        for backend in ["tree", "vm"] {
+            let normal = run(&script, backend);
+            let optimized = Command::new(env!("CARGO_BIN_EXE_reef"))
+                .args(["run", script.to_str().unwrap(), "-O", "--backend", backend])
+                .output()
+                .expect("failed to run reef");
+            assert_eq!(normal.stdout, optimized.stdout, "{}", script.display());
+            assert_eq!(normal.status.code(), optimized.status.code());
+        }
+    }
+}
+
+#[test]
+fn dump_ast_shows_folded_constants() {
+    let hello = Path::new(env!("CARGO_MANIFEST_DIR")).join("reef/hello.reef");
+    let dump = |flag: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_reef"))
+            .args(["run", hello.to_str().unwrap(), flag])
+            .output()
+            .expect("failed to run reef");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let parsed = dump("--dump-ast");
+    let optimized = dump("--dump-ast=optimized");
+    assert!(parsed.contains("(expr (* 2 4))"));
+    assert!(optimized.contains("(expr 8)"));
+    assert!(optimized.contains("(var another_var 450)"));
+}
+
+#[test]
+fn deep_recursion_is_a_runtime_error() {
+    let path = std::env::temp_dir().join(format!("reef_depth_{}.reef", std::process::id()));
+    fs::write(
+        &path,
+        "fun down(n) { if (n == 0) return 0; return 1 + down(n - 1); }\nprint down(50);\nprint down(1000000);\n",
+    )
+    .unwrap();
+    for backend in ["tree", "vm"] {
+        let output = run(&path, backend);
+        assert_eq!(output.status.code(), Some(70), "{}", backend);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "50\n");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("stack overflow"));
+
+        let limited = Command::new(env!("CARGO_BIN_EXE_reef"))
+            .args(["run", path.to_str().unwrap(), "--backend", backend])
+            .args(["--max-call-depth", "10"])
+            .output()
+            .expect("failed to run reef");
+        assert_eq!(limited.status.code(), Some(70), "{}", backend);
+        assert!(limited.stdout.is_empty());
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn imports_resolve_next_to_the_importer_then_reef_path() {
+    let root = std::env::temp_dir().join(format!("reef_imports_{}", std::process::id()));
+    let shared = root.join("shared");
+    fs::create_dir_all(root.join("app/lib")).unwrap();
+    fs::create_dir_all(&shared).unwrap();
+    fs::write(
+        root.join("app/main.reef"),
+        "import \"lib/util.reef\" as util;\n\
+         from \"lib/util.reef\" import double;\n\
+         from \"greet.reef\" import greet;\n\
+         print util.double(util.base);\n\
+         print double(2);\n\
+         print greet(\"reef\");\n\
+         print util.hidden;\n",
+    )
+    .unwrap();
+    fs::write(
+        root.join("app/lib/util.reef"),
+        "var base = 21;\nfun double(n) { return n * 2; }\n",
+    )
+    .unwrap();
+    fs::write(
+        shared.join("greet.reef"),
+        "fun greet(name) { return \"hello, \" + name; }\n",
+    )
+    .unwrap();
+    fs::write(root.join("app/a.reef"), "import \"b.reef\" as b;\n").unwrap();
+    fs::write(root.join("app/b.reef"), "import \"a.reef\" as a;\n").unwrap();
+    fs::write(
+        root.join("app/missing.reef"),
+        "import \"nope.reef\" as nope;\n",
+    )
+    .unwrap();
+
+    let run_in = |script: &str| {
+        Command::new(env!("CARGO_BIN_EXE_reef"))
+            .args(["run", root.join("app").join(script).to_str().unwrap()])
+            .env("REEF_PATH", &shared)
+            .output()
+            .expect("failed to run reef")
+    };
+    let output = run_in("main.reef");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "42\n4\nhello, reef\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has no export 'hidden'"));
+
+    let cycle = run_in("a.reef");
+    assert_eq!(cycle.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&cycle.stderr).contains("import cycle: "));
+    let missing = run_in("missing.reef");
+    assert_eq!(missing.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("module 'nope.reef' not found"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn uncaught_throw_is_a_runtime_error() {
+    let path = std::env::temp_dir().join(format!("reef_throw_{}.reef", std::process::id()));
+    fs::write(
+        &path,
+        "try { print -nil; } catch (e) { print e.kind; }\n\
+         try { throw \"boom\"; } finally { print \"cleanup\"; }\n\
+         print \"unreachable\";\n",
+    )
+    .unwrap();
+    let output = run(&path, "tree");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "RuntimeError\ncleanup\n"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("[line 2] Error: boom\n"), "{}", stderr);
+
+    let vm = run(&path, "vm");
+    assert!(String::from_utf8_lossy(&vm.stderr).contains("not supported by the vm backend"));
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,172 @@
+use std::io::{BufReader, Cursor};
+use std::{env, fs};
+
+use reef_interpreter::dap::DapServer;
+use reef_interpreter::lsp::transport::read_message;
+use serde_json::{Value, json};
+
+const SOURCE: &str = "fun square(n) {
+    var result = n * n;
+    return result;
+}
+var total = 0;
+total = total + square(3);
+print total;
+print \"done\";
+";
+
+fn write_program(name: &str, source: &str) -> String {
+    let path = env::temp_dir().join(format!("reef-dap-{}-{}.reef", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+fn request(seq: u64, command: &str, arguments: Value) -> String {
+    let body = json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })
+        .to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn session(messages: &[String]) -> Vec<Value> {
+    let mut server = DapServer::new(BufReader::new(Cursor::new(messages.concat())), Vec::new());
+    server.run().unwrap();
+    let mut reader = BufReader::new(Cursor::new(server.into_writer()));
+    let mut out = Vec::new();
+    while let Some(message) = read_message(&mut reader).unwrap() {
+        out.push(message);
+    }
+    out
+}
+
+fn response(messages: &[Value], seq: u64) -> &Value {
+    messages
+        .iter()
+        .find(|m| m["type"] == "response" && m["request_seq"] == seq)
+        .expect("response for request")
+}
+
+fn events<'a>(messages: &'a [Value], event: &str) -> Vec<&'a Value> {
+    messages.iter().filter(|m| m["event"] == event).collect()
+}
+
+fn output(messages: &[Value]) -> String {
+    events(messages, "output")
+        .iter()
+        .filter(|e| e["body"]["category"] == "stdout")
+        .map(|e| e["body"]["output"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_breakpoint_inspect_and_step() {
+    let program = write_program("breakpoint", SOURCE);
+    let messages = session(&[
+        request(1, "initialize", json!({ "adapterID": "reef" })),
+        request(2, "launch", json!({ "program": program })),
+        request(
+            3,
+            "setBreakpoints",
+            json!({ "source": { "path": program }, "breakpoints": [{ "line": 3 }] }),
+        ),
+        request(4, "configurationDone", json!({})),
+        // paused on `return result;` inside square
+        request(5, "stackTrace", json!({ "threadId": 1 })),
+        request(6, "scopes", json!({ "frameId": 0 })),
+        request(7, "variables", json!({ "variablesReference": 1 })),
+        request(20, "variables", json!({ "variablesReference": 0 })),
+        request(
+            8,
+            "evaluate",
+            json!({ "expression": "result + n", "frameId": 0 }),
+        ),
+        request(
+            9,
+            "evaluate",
+            json!({ "expression": "total", "frameId": 1 }),
+        ),
+        request(10, "stepOut", json!({ "threadId": 1 })),
+        request(11, "next", json!({ "threadId": 1 })),
+        request(12, "continue", json!({ "threadId": 1 })),
+        request(13, "disconnect", json!({})),
+    ]);
+
+    assert_eq!(events(&messages, "initialized").len(), 1);
+    let stopped = events(&messages, "stopped");
+    let reasons: Vec<&str> = stopped
+        .iter()
+        .map(|e| e["body"]["reason"].as_str().unwrap())
+        .collect();
+    assert_eq!(reasons, vec!["breakpoint", "step", "step"]);
+
+    let frames = response(&messages, 5)["body"]["stackFrames"]
+        .as_array()
+        .unwrap();
+    let names: Vec<(&str, u64)> = frames
+        .iter()
+        .map(|f| (f["name"].as_str().unwrap(), f["line"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(names, vec![("square", 3), ("main", 6)]);
+
+    let scopes = response(&messages, 6)["body"]["scopes"].as_array().unwrap();
+    assert_eq!(scopes[0]["name"], "Locals");
+    assert_eq!(scopes.last().unwrap()["name"], "Globals");
+
+    let variables = &response(&messages, 7)["body"]["variables"];
+    assert_eq!(
+        variables,
+        &json!([
+            { "name": "n", "value": "3", "variablesReference": 0 },
+            { "name": "result", "value": "9", "variablesReference": 0 },
+        ])
+    );
+    assert_eq!(response(&messages, 20)["success"], false);
+    assert_eq!(response(&messages, 8)["body"]["result"], "12");
+    assert_eq!(response(&messages, 9)["body"]["result"], "0");
+
+    assert_eq!(output(&messages), "9\ndone\n");
+    assert_eq!(events(&messages, "exited")[0]["body"]["exitCode"], 0);
+    assert_eq!(events(&messages, "terminated").len(), 1);
+}
+
+#[test]
+fn test_stop_on_entry_and_disconnect() {
+    let program = write_program("entry", SOURCE);
+    let messages = session(&[
+        request(1, "initialize", json!({})),
+        request(
+            2,
+            "launch",
+            json!({ "program": program, "stopOnEntry": true }),
+        ),
+        request(3, "configurationDone", json!({})),
+        request(4, "disconnect", json!({})),
+    ]);
+    let stopped = events(&messages, "stopped");
+    assert_eq!(stopped.len(), 1);
+    assert_eq!(stopped[0]["body"]["reason"], "entry");
+    assert_eq!(response(&messages, 4)["success"], true);
+    assert!(output(&messages).is_empty());
+    assert!(events(&messages, "terminated").is_empty());
+}
+
+#[test]
+fn test_scripts_cant_catch_a_disconnect() {
+    let program = write_program(
+        "catch",
+        "try {\n    print 1;\n} catch (e) {\n    print \"caught\";\n}\nprint \"after\";\n",
+    );
+    let messages = session(&[
+        request(1, "initialize", json!({})),
+        request(2, "launch", json!({ "program": program })),
+        request(
+            3,
+            "setBreakpoints",
+            json!({ "source": { "path": program }, "breakpoints": [{ "line": 2 }] }),
+        ),
+        request(4, "configurationDone", json!({})),
+        request(5, "disconnect", json!({})),
+    ]);
+    assert_eq!(events(&messages, "stopped").len(), 1);
+    assert_eq!(response(&messages, 5)["success"], true);
+    assert!(output(&messages).is_empty());
+}
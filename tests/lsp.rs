@@ -0,0 +1,197 @@
+use std::io::{BufReader, Cursor};
+
+use reef_interpreter::lsp::{Server, transport::read_message};
+use serde_json::{Value, json};
+
+const URI: &str = "file:///test.reef";
+
+const SOURCE: &str = "// adds two numbers
+// and returns the sum
+fun add(a, b) {
+    var total = a + b;
+    return total;
+}
+var result = add(1, 2);
+print result;
+";
+
+fn frame(message: Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn request(id: u64, method: &str, params: Value) -> String {
+    frame(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+}
+
+fn notification(method: &str, params: Value) -> String {
+    frame(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+}
+
+fn open(text: &str) -> String {
+    notification(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": URI, "languageId": "reef", "version": 1, "text": text } }),
+    )
+}
+
+fn at(line: u64, character: u64) -> Value {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+// runs a whole session and returns every message the server wrote
+fn session(messages: &[String]) -> Vec<Value> {
+    let input = messages.concat();
+    let mut server = Server::new(BufReader::new(Cursor::new(input)), Vec::new());
+    server.run().unwrap();
+    let output = server.into_writer();
+    let mut reader = BufReader::new(Cursor::new(output));
+    let mut responses = Vec::new();
+    while let Some(message) = read_message(&mut reader).unwrap() {
+        responses.push(message);
+    }
+    responses
+}
+
+fn response(responses: &[Value], id: u64) -> &Value {
+    &responses
+        .iter()
+        .find(|r| r["id"] == id)
+        .expect("response for request")["result"]
+}
+
+#[test]
+fn test_initialize_and_shutdown() {
+    let responses = session(&[
+        request(1, "initialize", json!({ "capabilities": {} })),
+        notification("initialized", json!({})),
+        request(2, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ]);
+    let capabilities = &response(&responses, 1)["capabilities"];
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(response(&responses, 2), &Value::Null);
+}
+
+#[test]
+fn test_malformed_messages_get_a_parse_error() {
+    let responses = session(&[
+        "Content-Length: 9\r\n\r\n{\"id\": 1,".to_string(),
+        request(2, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ]);
+    assert_eq!(responses[0]["id"], Value::Null);
+    assert_eq!(responses[0]["error"]["code"], -32700);
+    assert_eq!(response(&responses, 2), &Value::Null);
+}
+
+#[test]
+fn test_publishes_parse_diagnostics() {
+    let responses = session(&[
+        open("var a = 1;\nprint a\n"),
+        notification("exit", Value::Null),
+    ]);
+    let published = &responses[0];
+    assert_eq!(published["method"], "textDocument/publishDiagnostics");
+    let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 2);
+
+    let responses = session(&[open(SOURCE), notification("exit", Value::Null)]);
+    assert_eq!(responses[0]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn test_document_symbols() {
+    let responses = session(&[
+        open(SOURCE),
+        request(
+            1,
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": URI } }),
+        ),
+    ]);
+    let symbols = response(&responses, 1).as_array().unwrap();
+    let names: Vec<&str> = symbols
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["add", "result"]);
+    assert_eq!(symbols[0]["children"][0]["name"], "total");
+}
+
+#[test]
+fn test_definition_and_references() {
+    let responses = session(&[
+        open(SOURCE),
+        // `add` in `var result = add(1, 2);`
+        request(1, "textDocument/definition", at(6, 14)),
+        // `a` in `var total = a + b;`
+        request(2, "textDocument/definition", at(3, 16)),
+        request(
+            3,
+            "textDocument/references",
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": 3, "character": 9 },
+                "context": { "includeDeclaration": true },
+            }),
+        ),
+    ]);
+    let add = response(&responses, 1);
+    assert_eq!(add["range"]["start"], json!({ "line": 2, "character": 4 }));
+    let param = response(&responses, 2);
+    assert_eq!(
+        param["range"]["start"],
+        json!({ "line": 2, "character": 8 })
+    );
+    let references = response(&responses, 3).as_array().unwrap();
+    let lines: Vec<u64> = references
+        .iter()
+        .map(|r| r["range"]["start"]["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(lines, vec![3, 4]);
+}
+
+#[test]
+fn test_hover_and_completion() {
+    let responses = session(&[
+        open(SOURCE),
+        request(1, "textDocument/hover", at(6, 14)),
+        request(2, "textDocument/completion", at(7, 0)),
+    ]);
+    let hover = response(&responses, 1)["contents"]["value"]
+        .as_str()
+        .unwrap();
+    assert!(hover.contains("fun add(a, b)"));
+    assert!(hover.contains("arity: 2"));
+    assert!(hover.contains("adds two numbers\nand returns the sum"));
+
+    let labels: Vec<&str> = response(&responses, 2)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    for expected in ["while", "clock", "add", "result"] {
+        assert!(
+            labels.contains(&expected),
+            "missing completion {}",
+            expected
+        );
+    }
+
+    let responses = session(&[
+        open("fun f(a, b = 1, ...rest) {}\nfun g(a, b = 1) {}\n"),
+        request(1, "textDocument/hover", at(0, 4)),
+        request(2, "textDocument/hover", at(1, 4)),
+    ]);
+    let hover = |id| {
+        response(&responses, id)["contents"]["value"]
+            .as_str()
+            .unwrap()
+    };
+    assert!(hover(1).contains("arity: at least 1"));
+    assert!(hover(2).contains("arity: 1 to 2"));
+}